@@ -1,4 +1,9 @@
 use std::process;
+use std::sync::OnceLock;
+use unicode_normalization::UnicodeNormalization;
+
+use crate::charset;
+use crate::eff_wordlists::{EFF_LARGE, EFF_SHORT_1, EFF_SHORT_2};
 
 
 /* Complete printable ASCII charset */
@@ -24,7 +29,7 @@ pub fn alphabet_ascii_get_element(n: usize) -> Option<String> {
     Some(str)
 }
 
-/* "Normal characters" */
+/* "Normal characters", the same symbols as base62 */
 
 static ALPHABET_NORMAL: &[char] = &[
     '0','1','2','3','4','5','6','7','8','9',
@@ -46,7 +51,9 @@ pub fn alphabet_normal_get_element(n: usize) -> Option<String> {
 }
 
 
-/* Common and safe characters to use with various different keymaps. The default */
+/* Common and safe characters to use with various different keymaps. The default.
+   A subset of --layouts us,fi with look-alike characters (0 8 l B I O) and the y/z keys removed.
+*/
 
 static ALPHABET_COMMONSAFE: &[char] = &[
     '!','#','%',',','.','1','2','3','4','5','6','7','9',
@@ -68,6 +75,184 @@ pub fn alphabet_commonsafe_get_element(n: usize) -> Option<String> {
 }
 
 
+/* Hexadecimal, lowercase */
+
+static ALPHABET_HEX: &[char] = &[
+    '0','1','2','3','4','5','6','7','8','9','a','b','c','d','e','f'
+];
+
+pub fn alphabet_hex_get_count() -> usize {
+    ALPHABET_HEX.len()
+}
+
+pub fn alphabet_hex_get_element(n: usize) -> Option<String> {
+    if n >= ALPHABET_HEX.len() {
+        eprintln!("Error: Attempting to access non-existent charater.");
+        process::exit(1);
+    }
+    let c = ALPHABET_HEX[n];
+    Some(c.to_string())
+}
+
+
+/* RFC 4648 base32 */
+
+static ALPHABET_BASE32: &[char] = &[
+    'A','B','C','D','E','F','G','H','I','J','K','L','M','N','O','P','Q','R','S','T','U','V','W','X','Y','Z','2','3','4','5','6','7'
+];
+
+pub fn alphabet_base32_get_count() -> usize {
+    ALPHABET_BASE32.len()
+}
+
+pub fn alphabet_base32_get_element(n: usize) -> Option<String> {
+    if n >= ALPHABET_BASE32.len() {
+        eprintln!("Error: Attempting to access non-existent charater.");
+        process::exit(1);
+    }
+    let c = ALPHABET_BASE32[n];
+    Some(c.to_string())
+}
+
+
+/* Crockford base32. No I, L, O or U */
+
+static ALPHABET_CROCKFORD32: &[char] = &[
+    '0','1','2','3','4','5','6','7','8','9','A','B','C','D','E','F','G','H','J','K','M','N','P','Q','R','S','T','V','W','X','Y','Z'
+];
+
+pub fn alphabet_crockford32_get_count() -> usize {
+    ALPHABET_CROCKFORD32.len()
+}
+
+pub fn alphabet_crockford32_get_element(n: usize) -> Option<String> {
+    if n >= ALPHABET_CROCKFORD32.len() {
+        eprintln!("Error: Attempting to access non-existent charater.");
+        process::exit(1);
+    }
+    let c = ALPHABET_CROCKFORD32[n];
+    Some(c.to_string())
+}
+
+
+/* z-base-32, the human-oriented base32 ordering */
+
+static ALPHABET_ZBASE32: &[char] = &[
+    'y','b','n','d','r','f','g','8','e','j','k','m','c','p','q','x','o','t','1','u','w','i','s','z','a','3','4','5','h','7','6','9'
+];
+
+pub fn alphabet_zbase32_get_count() -> usize {
+    ALPHABET_ZBASE32.len()
+}
+
+pub fn alphabet_zbase32_get_element(n: usize) -> Option<String> {
+    if n >= ALPHABET_ZBASE32.len() {
+        eprintln!("Error: Attempting to access non-existent charater.");
+        process::exit(1);
+    }
+    let c = ALPHABET_ZBASE32[n];
+    Some(c.to_string())
+}
+
+
+/* Base58 as used by Bitcoin. No 0, O, I or l */
+
+static ALPHABET_BASE58: &[char] = &[
+    '1','2','3','4','5','6','7','8','9','A','B','C','D','E','F','G','H','J','K','L','M','N','P','Q','R','S','T','U','V','W','X','Y',
+    'Z','a','b','c','d','e','f','g','h','i','j','k','m','n','o','p','q','r','s','t','u','v','w','x','y','z'
+];
+
+pub fn alphabet_base58_get_count() -> usize {
+    ALPHABET_BASE58.len()
+}
+
+pub fn alphabet_base58_get_element(n: usize) -> Option<String> {
+    if n >= ALPHABET_BASE58.len() {
+        eprintln!("Error: Attempting to access non-existent charater.");
+        process::exit(1);
+    }
+    let c = ALPHABET_BASE58[n];
+    Some(c.to_string())
+}
+
+
+/* RFC 4648 base64url */
+
+static ALPHABET_BASE64URL: &[char] = &[
+    'A','B','C','D','E','F','G','H','I','J','K','L','M','N','O','P','Q','R','S','T','U','V','W','X','Y','Z','a','b','c','d','e','f',
+    'g','h','i','j','k','l','m','n','o','p','q','r','s','t','u','v','w','x','y','z','0','1','2','3','4','5','6','7','8','9','-','_'
+];
+
+pub fn alphabet_base64url_get_count() -> usize {
+    ALPHABET_BASE64URL.len()
+}
+
+pub fn alphabet_base64url_get_element(n: usize) -> Option<String> {
+    if n >= ALPHABET_BASE64URL.len() {
+        eprintln!("Error: Attempting to access non-existent charater.");
+        process::exit(1);
+    }
+    let c = ALPHABET_BASE64URL[n];
+    Some(c.to_string())
+}
+
+
+
+/* Pronounceable syllables, CV and CVC.
+   Every phoneme is a single letter and every syllable starts with a consonant, so a string of syllables
+   can be split back in only one way: a consonant followed by a vowel starts a syllable, any other consonant
+   closes the previous one. Each syllable is then one element, and the syllable count gives the exact entropy.
+*/
+
+static FI_ONSETS: &[char] = &['d','h','j','k','l','m','n','p','r','s','t','v'];
+static FI_VOWELS: &[char] = &['a','e','i','o','u','y','ä','ö'];
+static FI_CODAS: &[char] = &['h','k','l','m','n','p','r','s','t'];
+
+static EN_ONSETS: &[char] = &['b','d','f','g','h','j','k','l','m','n','p','r','s','t','v','w','y','z'];
+static EN_VOWELS: &[char] = &['a','e','i','o','u'];
+static EN_CODAS: &[char] = &['b','d','f','g','k','l','m','n','p','r','s','t','x','z'];
+
+fn syllable_count(onsets: &[char], vowels: &[char], codas: &[char]) -> usize {
+    onsets.len() * vowels.len() * (1 + codas.len())
+}
+
+/* Syllables 0..CV are the CV ones, the rest CVC */
+fn syllable_element(n: usize, onsets: &[char], vowels: &[char], codas: &[char]) -> Option<String> {
+    if n >= syllable_count(onsets, vowels, codas) {
+        eprintln!("Error: Attempting to access non-existent charater.");
+        process::exit(1);
+    }
+
+    let cv_count = onsets.len() * vowels.len();
+    let mut syllable = String::new();
+    if n < cv_count {
+        syllable.push(onsets[n / vowels.len()]);
+        syllable.push(vowels[n % vowels.len()]);
+    } else {
+        let m = n - cv_count;
+        syllable.push(onsets[m / (vowels.len() * codas.len())]);
+        syllable.push(vowels[(m / codas.len()) % vowels.len()]);
+        syllable.push(codas[m % codas.len()]);
+    }
+    Some(syllable)
+}
+
+pub fn alphabet_pronounceable_fi_get_count() -> usize {
+    syllable_count(FI_ONSETS, FI_VOWELS, FI_CODAS)
+}
+
+pub fn alphabet_pronounceable_fi_get_element(n: usize) -> Option<String> {
+    syllable_element(n, FI_ONSETS, FI_VOWELS, FI_CODAS)
+}
+
+pub fn alphabet_pronounceable_en_get_count() -> usize {
+    syllable_count(EN_ONSETS, EN_VOWELS, EN_CODAS)
+}
+
+pub fn alphabet_pronounceable_en_get_element(n: usize) -> Option<String> {
+    syllable_element(n, EN_ONSETS, EN_VOWELS, EN_CODAS)
+}
+
 
 
 /* Assembly wordlist ripped from: https://gitlab.com/Aketzu/ruttu/-/blob/master/lib/codegen.rb */
@@ -107,9 +292,38 @@ pub fn alphabet_assembly_get_element(n: usize) -> Option<String> {
     Some(s)
 }
 
+/* Assembly wordlist folded to ASCII (ä→a, ö→o) for US keyboards.
+   Words that become equal when folded are kept once, so the count is that of the unique folded words.
+*/
+
+fn assembly_ascii_words() -> &'static Vec<String> {
+    static WORDS: OnceLock<Vec<String>> = OnceLock::new();
+    WORDS.get_or_init(|| {
+        let mut words: Vec<String> = ALPHABET_ASSEMBLY.iter().map(|word| word.to_string()).collect();
+        charset::fold_elements(&mut words);
+        words
+    })
+}
+
+pub fn alphabet_assembly_ascii_get_count() -> usize {
+    assembly_ascii_words().len()
+}
+
+pub fn alphabet_assembly_ascii_get_element(n: usize) -> Option<String> {
+    if n >= assembly_ascii_words().len() {
+        eprintln!("Error: Attempting to access non-existent charater.");
+        process::exit(1);
+    }
+    Some(assembly_ascii_words()[n].clone())
+}
+
 /* Finnish wordlist from Kotus
    processed with:
    cat nykysuomensanalista2022.csv | grep -ve "-" -e "z" -e "Z" -e "ä" -e "Ä" -e "ö" -e "Ö" -e "š" | awk {'print $1'} | tr '[:upper:]' '[:lower:]' | grep -wE '\w{3,8}' | sort | uniq > sanat.txt
+
+   This leaves out every word with ä, ö, z or š. The full vocabulary is not bundled, it can be built from the same file
+   and used with --wordlist, as is or folded to ASCII with --fold-ascii:
+   cat nykysuomensanalista2022.csv | grep -ve "-" | awk {'print $1'} | tr '[:upper:]' '[:lower:]' | grep -wE '\w{3,8}' | sort | uniq > sanat-full.txt
 */
 
 
@@ -129,4 +343,94 @@ pub fn alphabet_wordsfi_get_element(n: usize) -> Option<String> {
     }
     let s = ALPHABET_WORDSFI[n].to_string();
     Some(s)
-}
\ No newline at end of file
+}
+
+/* EFF diceware wordlists, see eff_wordlists.rs. The built-in lists keep their dice numbers for --dice. */
+
+pub fn alphabet_eff_large_get_count() -> usize {
+    EFF_LARGE.len()
+}
+
+pub fn alphabet_eff_large_get_element(n: usize) -> Option<String> {
+    if n >= EFF_LARGE.len() {
+        eprintln!("Error: Attempting to access non-existent charater.");
+        process::exit(1);
+    }
+    Some(EFF_LARGE[n].1.to_string())
+}
+
+pub fn alphabet_eff_short_get_count() -> usize {
+    EFF_SHORT_1.len()
+}
+
+pub fn alphabet_eff_short_get_element(n: usize) -> Option<String> {
+    if n >= EFF_SHORT_1.len() {
+        eprintln!("Error: Attempting to access non-existent charater.");
+        process::exit(1);
+    }
+    Some(EFF_SHORT_1[n].1.to_string())
+}
+
+pub fn alphabet_eff_short2_get_count() -> usize {
+    EFF_SHORT_2.len()
+}
+
+pub fn alphabet_eff_short2_get_element(n: usize) -> Option<String> {
+    if n >= EFF_SHORT_2.len() {
+        eprintln!("Error: Attempting to access non-existent charater.");
+        process::exit(1);
+    }
+    Some(EFF_SHORT_2[n].1.to_string())
+}
+
+/* The dice numbers of a built-in wordlist that has them, in the order of its elements */
+
+pub fn alphabet_dice_numbers(name: &str) -> Option<Vec<String>> {
+    let list = match name {
+        "words-en-eff" => EFF_LARGE,
+        "words-en-short" => EFF_SHORT_1,
+        "words-en-short2" => EFF_SHORT_2,
+        _ => return None,
+    };
+    Some(list.iter().map(|(dice, _)| dice.to_string()).collect())
+}
+
+/* Names of the built-in alphabets, as accepted by --alphabet */
+
+pub const ALPHABET_NAMES: &[&str] = &["words-fi", "words-en-eff", "words-en-short", "words-en-short2", "commonsafe", "normal", "ascii", "assembly", "assembly-ascii",
+    "hex", "base32", "crockford32", "zbase32", "base58", "base64url", "pronounceable-fi", "pronounceable-en"];
+
+pub type AlphabetCountFn = fn() -> usize;
+pub type AlphabetElementFn = fn(usize) -> Option<String>;
+
+/* Returns the count and element functions for the named alphabet */
+
+pub fn alphabet_get_functions(name: &str) -> Option<(AlphabetCountFn, AlphabetElementFn)> {
+    match name {
+        "words-fi" => Some((alphabet_wordsfi_get_count, alphabet_wordsfi_get_element)),
+        "words-en-eff" => Some((alphabet_eff_large_get_count, alphabet_eff_large_get_element)),
+        "words-en-short" => Some((alphabet_eff_short_get_count, alphabet_eff_short_get_element)),
+        "words-en-short2" => Some((alphabet_eff_short2_get_count, alphabet_eff_short2_get_element)),
+        "commonsafe" => Some((alphabet_commonsafe_get_count, alphabet_commonsafe_get_element)),
+        "normal" => Some((alphabet_normal_get_count, alphabet_normal_get_element)),
+        "ascii" => Some((alphabet_ascii_get_count, alphabet_ascii_get_element)),
+        "assembly" => Some((alphabet_assembly_get_count, alphabet_assembly_get_element)),
+        "assembly-ascii" => Some((alphabet_assembly_ascii_get_count, alphabet_assembly_ascii_get_element)),
+        "hex" => Some((alphabet_hex_get_count, alphabet_hex_get_element)),
+        "base32" => Some((alphabet_base32_get_count, alphabet_base32_get_element)),
+        "crockford32" => Some((alphabet_crockford32_get_count, alphabet_crockford32_get_element)),
+        "zbase32" => Some((alphabet_zbase32_get_count, alphabet_zbase32_get_element)),
+        "base58" => Some((alphabet_base58_get_count, alphabet_base58_get_element)),
+        "base64url" => Some((alphabet_base64url_get_count, alphabet_base64url_get_element)),
+        "pronounceable-fi" => Some((alphabet_pronounceable_fi_get_count, alphabet_pronounceable_fi_get_element)),
+        "pronounceable-en" => Some((alphabet_pronounceable_en_get_count, alphabet_pronounceable_en_get_element)),
+        _ => None,
+    }
+}
+
+/* Returns all elements of the named alphabet, normalized to NFC */
+
+pub fn alphabet_get_elements(name: &str) -> Option<Vec<String>> {
+    let (alphabet_count, alphabet_item) = alphabet_get_functions(name)?;
+    Some((0..alphabet_count()).map(|n| alphabet_item(n).unwrap().nfc().collect()).collect())
+}
@@ -0,0 +1,73 @@
+/* alphabet_info.rs
+
+   "keygen alphabets" lists the built-in alphabets with their size, bits per symbol, symbols needed
+   for common security levels, character classes and ambiguous glyphs. "keygen alphabets show NAME"
+   prints every symbol of one alphabet, one per line, for review or diffing.
+*/
+
+use crate::alphabet;
+use crate::normalize;
+use crate::policy::{CharClass, CHAR_CLASSES};
+
+/* Characters easily mistaken for each other in print or on screen */
+const AMBIGUOUS_GLYPHS: &str = "0Oo1lI|5S2Z8B6G`'\"";
+
+struct AlphabetInfo {
+    name: &'static str,
+    count: usize,
+    bits_per_symbol: f64,
+    symbols_for: [u32; 3],
+    average_length: f64,
+    classes: Vec<&'static str>,
+    ambiguous: String,
+}
+
+fn describe(name: &'static str) -> AlphabetInfo {
+    let elements = alphabet::alphabet_get_elements(name).unwrap();
+    let symbols_for = [64, 128, 256].map(|bits| crate::elements_for_bits(bits, elements.len()));
+    let average_graphemes = elements.iter().map(|element| normalize::grapheme_count(element)).sum::<usize>() as f64 / elements.len() as f64;
+
+    let classes = CHAR_CLASSES.iter()
+        .filter(|&&class| elements.iter().any(|element| element.chars().any(|c| CharClass::of(c) == class)))
+        .map(|class| class.name())
+        .collect();
+    // Within words the letters are read in context, only single symbols can be mistaken
+    let ambiguous = if elements.iter().all(|element| normalize::grapheme_count(element) == 1) {
+        AMBIGUOUS_GLYPHS.chars().filter(|&glyph| elements.iter().any(|element| element.contains(glyph))).collect()
+    } else {
+        String::new()
+    };
+
+    AlphabetInfo {
+        name,
+        count: elements.len(),
+        bits_per_symbol: (elements.len() as f64).log2(),
+        symbols_for,
+        average_length: average_graphemes * symbols_for[1] as f64,
+        classes,
+        ambiguous,
+    }
+}
+
+/* Prints the table of all built-in alphabets */
+pub fn print_alphabets() {
+    println!("{:<18} {:>8} {:>9} {:>8} {:>8} {:>8} {:>12}  {:<28} Ambiguous",
+        "Alphabet", "Symbols", "Bits/sym", "64 bit", "128 bit", "256 bit", "Len@128 bit", "Classes");
+    for name in alphabet::ALPHABET_NAMES {
+        let info = describe(name);
+        println!("{:<18} {:>8} {:>9.4} {:>8} {:>8} {:>8} {:>12.1}  {:<28} {}",
+            info.name, info.count, info.bits_per_symbol,
+            info.symbols_for[0], info.symbols_for[1], info.symbols_for[2],
+            info.average_length, info.classes.join(","),
+            if info.ambiguous.is_empty() { "-".to_string() } else { info.ambiguous });
+    }
+}
+
+/* Prints every symbol of the named alphabet, one per line */
+pub fn print_alphabet(name: &str) -> Result<(), String> {
+    let elements = alphabet::alphabet_get_elements(name).ok_or_else(|| format!("unknown alphabet \"{}\"", name))?;
+    for element in elements {
+        println!("{}", element);
+    }
+    Ok(())
+}
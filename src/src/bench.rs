@@ -0,0 +1,146 @@
+/* bench.rs
+
+   Measures throughput and per-call latency of the random sources, the HMAC DRBG
+   and full password generation with each alphabet. Useful for setting timeouts and
+   picking alphabets for bulk jobs on a given host.
+*/
+
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use crate::alphabet;
+use crate::random;
+use crate::random::hmac_drbg::HmacDrbg;
+
+type SourceFn = fn() -> Option<u64>;
+
+pub enum BenchOutput {
+    Table,
+    Json,
+}
+
+impl FromStr for BenchOutput {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "table" => Ok(BenchOutput::Table),
+            "json" => Ok(BenchOutput::Json),
+            _ => Err(()),
+        }
+    }
+}
+
+struct BenchResult {
+    name: String,
+    iterations: u32,
+    bytes: u64,
+    total: Duration,
+    latencies: Vec<Duration>,
+}
+
+impl BenchResult {
+    fn ops_per_sec(&self) -> f64 {
+        self.iterations as f64 / self.total.as_secs_f64()
+    }
+
+    fn bytes_per_sec(&self) -> f64 {
+        self.bytes as f64 / self.total.as_secs_f64()
+    }
+
+    // Nearest-rank percentile of the sorted latencies, in microseconds
+    fn percentile_us(&self, p: f64) -> f64 {
+        let rank = ((p / 100.0) * self.latencies.len() as f64).ceil() as usize;
+        let index = rank.clamp(1, self.latencies.len()) - 1;
+        self.latencies[index].as_secs_f64() * 1_000_000.0
+    }
+}
+
+
+/* Runs f for the given amount of iterations. f returns the amount of bytes it produced. */
+fn measure<F: FnMut() -> usize>(name: &str, iterations: u32, mut f: F) -> BenchResult {
+    let mut latencies = Vec::with_capacity(iterations as usize);
+    let mut bytes: u64 = 0;
+
+    let start = Instant::now();
+    for _ in 0..iterations {
+        let call_start = Instant::now();
+        bytes += f() as u64;
+        latencies.push(call_start.elapsed());
+    }
+    let total = start.elapsed();
+
+    latencies.sort();
+
+    BenchResult {
+        name: name.to_string(),
+        iterations,
+        bytes,
+        total,
+        latencies,
+    }
+}
+
+
+fn print_table(results: &[BenchResult]) {
+    println!("{:<24} {:>10} {:>12} {:>14} {:>10} {:>10} {:>10} {:>10}",
+        "benchmark", "calls", "ops/s", "bytes/s", "p50 us", "p90 us", "p99 us", "max us");
+
+    for r in results {
+        println!("{:<24} {:>10} {:>12.1} {:>14.1} {:>10.2} {:>10.2} {:>10.2} {:>10.2}",
+            r.name, r.iterations, r.ops_per_sec(), r.bytes_per_sec(),
+            r.percentile_us(50.0), r.percentile_us(90.0), r.percentile_us(99.0), r.percentile_us(100.0));
+    }
+}
+
+fn print_json(results: &[BenchResult]) {
+    let entries: Vec<String> = results.iter().map(|r| {
+        format!("{{\"name\":\"{}\",\"calls\":{},\"ops_per_sec\":{:.1},\"bytes_per_sec\":{:.1},\"p50_us\":{:.2},\"p90_us\":{:.2},\"p99_us\":{:.2},\"max_us\":{:.2}}}",
+            r.name, r.iterations, r.ops_per_sec(), r.bytes_per_sec(),
+            r.percentile_us(50.0), r.percentile_us(90.0), r.percentile_us(99.0), r.percentile_us(100.0))
+    }).collect();
+
+    println!("[{}]", entries.join(","));
+}
+
+
+/* Benchmarks every source, the DRBG and password generation with each alphabet */
+pub fn run_bench(iterations: u32, bits: u32, output: BenchOutput) {
+    let sources: [(&str, SourceFn); 5] = [
+        ("os", random::generate_u64_os),
+        ("rdrand", random::generate_u64_rdrand),
+        ("cpujitter", random::generate_u64_cpujitter),
+        ("cpujitter-raw", random::generate_u64_cpujitter_raw),
+        ("combined", random::generate_u64),
+    ];
+
+    let mut results = Vec::new();
+
+    for (name, generator_fn) in sources.iter() {
+        results.push(measure(name, iterations, || {
+            generator_fn().unwrap();
+            8
+        }));
+    }
+
+    // Same usage as in generate_u64(): instantiate with a 1536 bit seed and pull 64 bits
+    let seed = [0x5au8; 192];
+    let personalization_string = [0xa5u8; 32];
+    results.push(measure("hmac-drbg", iterations, || {
+        let mut drbg = HmacDrbg::new(&seed, &personalization_string);
+        drbg.generate_bytes(8).len()
+    }));
+
+    for name in alphabet::ALPHABET_NAMES {
+        let elements = alphabet::alphabet_get_elements(name).unwrap();
+        let num_elements = crate::elements_for_bits(bits, elements.len());
+        let bench_name = format!("password-{}-{}", name, bits);
+        results.push(measure(&bench_name, iterations, || {
+            crate::generate_password(&elements, num_elements, "").len()
+        }));
+    }
+
+    match output {
+        BenchOutput::Table => print_table(&results),
+        BenchOutput::Json => print_json(&results),
+    }
+}
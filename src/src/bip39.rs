@@ -0,0 +1,192 @@
+/* bip39.rs
+
+   BIP-39 mnemonic generation and validation.
+   https://github.com/bitcoin/bips/blob/master/bip-0039.mediawiki
+
+   Entropy is drawn from the combined generator, the checksum is the first ENT/32 bits
+   of SHA-256 over the entropy. The seed is PBKDF2-HMAC-SHA512 with 2048 iterations over
+   the NFKD normalized mnemonic, salted with "mnemonic" + passphrase.
+*/
+
+use std::num::NonZeroU32;
+
+use ring::{digest, pbkdf2};
+use unicode_normalization::UnicodeNormalization;
+use zeroize::Zeroize;
+
+use crate::bip39_wordlists::*;
+use crate::random;
+
+pub const BIP39_WORD_COUNTS: &[&str] = &["12", "15", "18", "21", "24"];
+pub const BIP39_LANGUAGES: &[&str] = &["en", "es", "fr", "it", "pt", "cs", "ja", "ko", "zh-hans", "zh-hant"];
+
+const PBKDF2_ROUNDS: u32 = 2048;
+
+pub fn bip39_get_wordlist(lang: &str) -> Option<&'static [&'static str]> {
+    match lang {
+        "en" => Some(BIP39_ENGLISH),
+        "es" => Some(BIP39_SPANISH),
+        "fr" => Some(BIP39_FRENCH),
+        "it" => Some(BIP39_ITALIAN),
+        "pt" => Some(BIP39_PORTUGUESE),
+        "cs" => Some(BIP39_CZECH),
+        "ja" => Some(BIP39_JAPANESE),
+        "ko" => Some(BIP39_KOREAN),
+        "zh-hans" => Some(BIP39_CHINESE_SIMPLIFIED),
+        "zh-hant" => Some(BIP39_CHINESE_TRADITIONAL),
+        _ => None,
+    }
+}
+
+/* Japanese mnemonics are written with an ideographic space between the words */
+pub fn word_separator(lang: &str) -> &'static str {
+    if lang == "ja" { "\u{3000}" } else { " " }
+}
+
+
+/* Creates a mnemonic of the given amount of words with entropy from the combined generator */
+pub fn generate_mnemonic(num_words: usize, lang: &str) -> Result<String, String> {
+    if !num_words.is_multiple_of(3) || !(12..=24).contains(&num_words) {
+        return Err(format!("invalid word count {}, use 12, 15, 18, 21 or 24", num_words));
+    }
+
+    // ENT = 32 * words / 3 bits
+    let mut entropy = random::generate_bytes(num_words * 4 / 3);
+
+    let mnemonic = mnemonic_from_entropy(&entropy, lang);
+    entropy.zeroize();
+    mnemonic
+}
+
+/* Encodes 128-256 bits of entropy as a mnemonic */
+pub fn mnemonic_from_entropy(entropy: &[u8], lang: &str) -> Result<String, String> {
+    let wordlist = bip39_get_wordlist(lang).ok_or_else(|| format!("unknown language {}", lang))?;
+    let mut bits = entropy_with_checksum(entropy);
+
+    let words: Vec<&str> = bits.chunks(11)
+        .map(|chunk| wordlist[chunk.iter().fold(0usize, |index, &bit| (index << 1) | bit as usize)])
+        .collect();
+    bits.zeroize();
+
+    Ok(words.join(word_separator(lang)))
+}
+
+/* Entropy bits followed by the checksum bits, one bit per element */
+fn entropy_with_checksum(entropy: &[u8]) -> Vec<u8> {
+    let hash = digest::digest(&digest::SHA256, entropy);
+    let checksum_bits = entropy.len() * 8 / 32;
+
+    let mut bits: Vec<u8> = Vec::with_capacity(entropy.len() * 8 + checksum_bits);
+    for byte in entropy {
+        for shift in (0..8).rev() {
+            bits.push((byte >> shift) & 1);
+        }
+    }
+    for i in 0..checksum_bits {
+        bits.push((hash.as_ref()[i / 8] >> (7 - i % 8)) & 1);
+    }
+
+    bits
+}
+
+
+/* Result of validating an existing mnemonic */
+pub struct Bip39Validation {
+    pub lang: &'static str,
+    pub num_words: usize,
+    pub entropy_bits: usize,
+}
+
+/* Checks the words and the checksum of a mnemonic. The language is detected if not given. */
+pub fn validate_mnemonic(mnemonic: &str, lang: Option<&str>) -> Result<Bip39Validation, String> {
+    let words: Vec<String> = mnemonic.split(|c: char| c.is_whitespace()).filter(|w| !w.is_empty()).map(|w| w.nfkd().collect()).collect();
+
+    if !words.len().is_multiple_of(3) || !(12..=24).contains(&words.len()) {
+        return Err(format!("mnemonic has {} words, expected 12, 15, 18, 21 or 24", words.len()));
+    }
+
+    let candidates: Vec<&'static str> = match lang {
+        Some(lang) => {
+            let lang = BIP39_LANGUAGES.iter().find(|l| **l == lang).ok_or_else(|| format!("unknown language {}", lang))?;
+            vec![*lang]
+        }
+        None => BIP39_LANGUAGES.to_vec(),
+    };
+
+    let mut words_matched = false;
+    let mut last_error = String::new();
+    for candidate in candidates {
+        let wordlist = bip39_get_wordlist(candidate).unwrap();
+        let normalized: Vec<String> = wordlist.iter().map(|w| w.nfkd().collect()).collect();
+
+        let mut indices = Vec::with_capacity(words.len());
+        for word in &words {
+            match normalized.iter().position(|w| w == word) {
+                Some(index) => indices.push(index),
+                None => {
+                    last_error = format!("word \"{}\" is not in the {} wordlist", word, candidate);
+                    break;
+                }
+            }
+        }
+        if indices.len() != words.len() {
+            continue;
+        }
+        words_matched = true;
+
+        // Split the 11 bit indices back to entropy and checksum
+        let total_bits = words.len() * 11;
+        let entropy_bits = total_bits * 32 / 33;
+        let mut bits: Vec<u8> = Vec::with_capacity(total_bits);
+        for index in &indices {
+            for shift in (0..11).rev() {
+                bits.push(((index >> shift) & 1) as u8);
+            }
+        }
+        let mut entropy: Vec<u8> = bits[..entropy_bits].chunks(8)
+            .map(|chunk| chunk.iter().fold(0u8, |byte, &bit| (byte << 1) | bit))
+            .collect();
+
+        let expected = entropy_with_checksum(&entropy);
+        let checksum_ok = expected == bits;
+        entropy.zeroize();
+        bits.zeroize();
+        if !checksum_ok {
+            continue;
+        }
+
+        return Ok(Bip39Validation {
+            lang: candidate,
+            num_words: words.len(),
+            entropy_bits,
+        });
+    }
+
+    if words_matched {
+        Err("checksum does not match".to_string())
+    } else if lang.is_none() {
+        Err("words do not match any supported wordlist".to_string())
+    } else {
+        Err(last_error)
+    }
+}
+
+
+/* Derives the 64 byte seed from the mnemonic and an optional passphrase */
+pub fn mnemonic_to_seed(mnemonic: &str, passphrase: &str) -> [u8; 64] {
+    let mut mnemonic: String = mnemonic.nfkd().collect();
+    let mut salt: String = format!("mnemonic{}", passphrase).nfkd().collect();
+    let mut seed = [0u8; 64];
+
+    pbkdf2::derive(
+        pbkdf2::PBKDF2_HMAC_SHA512,
+        NonZeroU32::new(PBKDF2_ROUNDS).unwrap(),
+        salt.as_bytes(),
+        mnemonic.as_bytes(),
+        &mut seed,
+    );
+
+    mnemonic.zeroize();
+    salt.zeroize();
+    seed
+}
@@ -0,0 +1,166 @@
+/* breach.rs
+
+   Offline lookup in the Have I Been Pwned password list, the SHA-1 version ordered by hash
+   (pwned-passwords-sha1-ordered-by-hash.txt, lines "HASH:COUNT"). Nothing is sent anywhere.
+
+   The file is memory mapped and binary searched. An optional index, FILE.idx, stores the byte offset
+   of the first line of every 4 hex digit prefix, so a lookup only searches within one prefix.
+   Index format: "KGHIBP1\0", the database length (u64 LE), then 65537 offsets (u64 LE).
+*/
+
+use std::fs::File;
+use std::io::Write;
+
+use memmap2::Mmap;
+use ring::digest;
+
+const INDEX_MAGIC: &[u8; 8] = b"KGHIBP1\0";
+const INDEX_ENTRIES: usize = 65537;
+
+pub struct BreachDb {
+    map: Mmap,
+    index: Option<Vec<u64>>,
+}
+
+fn index_path(path: &str) -> String {
+    format!("{}.idx", path)
+}
+
+fn hex_value(c: u8) -> Option<u32> {
+    (c as char).to_digit(16)
+}
+
+/* The 16 bit prefix of a line starting with a hex hash */
+fn line_prefix(line: &[u8]) -> Option<usize> {
+    if line.len() < 4 {
+        return None;
+    }
+    let mut prefix = 0;
+    for &c in &line[..4] {
+        prefix = prefix * 16 + hex_value(c)? as usize;
+    }
+    Some(prefix)
+}
+
+
+pub fn open(path: &str) -> Result<BreachDb, String> {
+    let file = File::open(path).map_err(|e| format!("Failed to open {}: {}", path, e))?;
+    // The file is only read, and a concurrent writer would only make lookups miss
+    let map = unsafe { Mmap::map(&file) }.map_err(|e| format!("Failed to map {}: {}", path, e))?;
+
+    let index = match std::fs::read(index_path(path)) {
+        Ok(data) if data.len() == 16 + INDEX_ENTRIES * 8
+            && &data[..8] == INDEX_MAGIC
+            && u64::from_le_bytes(data[8..16].try_into().unwrap()) == map.len() as u64 => {
+            Some(data[16..].chunks(8).map(|chunk| u64::from_le_bytes(chunk.try_into().unwrap())).collect())
+        }
+        Ok(_) => {
+            eprintln!("Warning: {} does not match the database, ignoring it. Rebuild it with --build-index.", index_path(path));
+            None
+        }
+        Err(_) => None,
+    };
+
+    Ok(BreachDb { map, index })
+}
+
+/* Writes FILE.idx, returns the number of lines indexed */
+pub fn build_index(path: &str) -> Result<usize, String> {
+    let db = open(path)?;
+    let data = &db.map[..];
+
+    let mut offsets: Vec<Option<u64>> = vec![None; INDEX_ENTRIES];
+    offsets[INDEX_ENTRIES - 1] = Some(data.len() as u64);
+
+    let mut lines = 0;
+    let mut previous = 0;
+    let mut start = 0;
+    while start < data.len() {
+        let end = data[start..].iter().position(|&c| c == b'\n').map_or(data.len(), |p| start + p);
+        let prefix = line_prefix(&data[start..end]).ok_or_else(|| format!("{}: line {} is not a SHA-1 hash", path, lines + 1))?;
+        if prefix < previous {
+            return Err(format!("{}: line {} is out of order, use the file ordered by hash", path, lines + 1));
+        }
+        if offsets[prefix].is_none() {
+            offsets[prefix] = Some(start as u64);
+        }
+        previous = prefix;
+        lines += 1;
+        start = end + 1;
+    }
+
+    // Prefixes without lines start where the next prefix starts
+    for i in (0..INDEX_ENTRIES - 1).rev() {
+        if offsets[i].is_none() {
+            offsets[i] = offsets[i + 1];
+        }
+    }
+
+    let mut output: Vec<u8> = Vec::with_capacity(16 + INDEX_ENTRIES * 8);
+    output.extend_from_slice(INDEX_MAGIC);
+    output.extend_from_slice(&(data.len() as u64).to_le_bytes());
+    for offset in offsets {
+        output.extend_from_slice(&offset.unwrap().to_le_bytes());
+    }
+
+    // Written beside the index and renamed over it, so a reader never sees a partial index
+    let temp_path = format!("{}.tmp", index_path(path));
+    let mut file = File::create(&temp_path).map_err(|e| format!("Failed to create {}: {}", temp_path, e))?;
+    let written = file.write_all(&output).and_then(|_| file.sync_all());
+    drop(file);
+    if let Err(e) = written {
+        let _ = std::fs::remove_file(&temp_path);
+        return Err(format!("Failed to write {}: {}", temp_path, e));
+    }
+    std::fs::rename(&temp_path, index_path(path)).map_err(|e| {
+        let _ = std::fs::remove_file(&temp_path);
+        format!("Failed to rename {} to {}: {}", temp_path, index_path(path), e)
+    })?;
+
+    Ok(lines)
+}
+
+
+impl BreachDb {
+    /* Occurrence count of the upper case hex SHA-1 hash, None if not in the list */
+    pub fn lookup_hash(&self, hash: &[u8]) -> Option<u64> {
+        let data = &self.map[..];
+        let (mut low, mut high) = match &self.index {
+            Some(index) => {
+                let prefix = line_prefix(hash)?;
+                (index[prefix] as usize, index[prefix + 1] as usize)
+            }
+            None => (0, data.len()),
+        };
+
+        while low < high {
+            let middle = low + (high - low) / 2;
+            let line_start = data[low..middle].iter().rposition(|&c| c == b'\n').map_or(low, |p| low + p + 1);
+            let line_end = data[middle..high].iter().position(|&c| c == b'\n').map_or(high, |p| middle + p);
+            let line = &data[line_start..line_end];
+
+            let line_hash = &line[..line.len().min(40)];
+            match line_hash.to_ascii_uppercase().as_slice().cmp(hash) {
+                std::cmp::Ordering::Equal => {
+                    let count = std::str::from_utf8(&line[line_hash.len()..]).ok()?
+                        .trim_start_matches(':')
+                        .trim()
+                        .parse::<u64>()
+                        .unwrap_or(1);
+                    return Some(count);
+                }
+                std::cmp::Ordering::Less => low = line_end + 1,
+                std::cmp::Ordering::Greater => high = line_start,
+            }
+        }
+
+        None
+    }
+
+    /* Occurrence count of the secret in the list */
+    pub fn lookup(&self, secret: &str) -> Option<u64> {
+        let hash = digest::digest(&digest::SHA1_FOR_LEGACY_USE_ONLY, secret.as_bytes());
+        let hex = hex::encode_upper(hash.as_ref());
+        self.lookup_hash(hex.as_bytes())
+    }
+}
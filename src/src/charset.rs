@@ -0,0 +1,113 @@
+/* charset.rs
+
+   Custom character sets given on the command line.
+   Syntax: plain characters, ranges like a-z or 0-9, and backslash escapes.
+   A backslash makes the next character literal, so "\-" is a dash and "\\" a backslash.
+   A dash at the start or end of the set is taken literally.
+*/
+
+use unicode_normalization::UnicodeNormalization;
+
+/* Parses a charset specification into a list of unique characters, keeping the first occurrence order */
+pub fn parse_charset(spec: &str) -> Result<Vec<char>, String> {
+    // Resolve escapes first, remembering which characters were escaped
+    let mut tokens: Vec<(char, bool)> = Vec::new();
+    // Precomposed and decomposed input give the same characters
+    let spec: String = spec.nfc().collect();
+    let mut chars = spec.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some(escaped) => tokens.push((escaped, true)),
+                None => return Err("charset ends with a lone backslash".to_string()),
+            }
+        } else {
+            tokens.push((c, false));
+        }
+    }
+
+    let mut result: Vec<char> = Vec::new();
+    let mut i = 0;
+    while i < tokens.len() {
+        let (c, _) = tokens[i];
+
+        // Range: x-y where the dash is not escaped and not the last character
+        if i + 2 < tokens.len() && tokens[i + 1] == ('-', false) {
+            let (end, _) = tokens[i + 2];
+            if end < c {
+                return Err(format!("invalid range {}-{}", c, end));
+            }
+            for r in c..=end {
+                push_unique(&mut result, r);
+            }
+            i += 3;
+            continue;
+        }
+
+        push_unique(&mut result, c);
+        i += 1;
+    }
+
+    if let Some(c) = result.iter().find(|c| c.is_control()) {
+        return Err(format!("charset contains a control character (U+{:04X})", *c as u32));
+    }
+
+    Ok(result)
+}
+
+fn push_unique(chars: &mut Vec<char>, c: char) {
+    if !chars.contains(&c) {
+        chars.push(c);
+    }
+}
+
+
+/* Adds the included characters to the alphabet. Elements already present are not duplicated. */
+pub fn include_chars(elements: &mut Vec<String>, include: &[char]) {
+    for c in include {
+        let element = c.to_string();
+        if !elements.contains(&element) {
+            elements.push(element);
+        }
+    }
+}
+
+/* Removes every element that contains any of the excluded characters.
+   For character alphabets this removes the characters themselves, for wordlists the words containing them.
+*/
+pub fn exclude_chars(elements: &mut Vec<String>, exclude: &[char]) {
+    elements.retain(|element| !element.chars().any(|c| exclude.contains(&c)));
+}
+
+/* Removes duplicate elements, keeping the first occurrence */
+pub fn dedup_elements(elements: &mut Vec<String>) {
+    let mut seen = std::collections::HashSet::new();
+    elements.retain(|element| seen.insert(element.clone()));
+}
+
+/* Folds the Finnish and Swedish letters to ASCII: ä→a, ö→o, å→a, š→s, ž→z */
+pub fn ascii_fold(element: &str) -> String {
+    element.chars().map(|c| match c {
+        'ä' | 'å' => 'a',
+        'Ä' | 'Å' => 'A',
+        'ö' => 'o',
+        'Ö' => 'O',
+        'š' => 's',
+        'Š' => 'S',
+        'ž' => 'z',
+        'Ž' => 'Z',
+        _ => c,
+    }).collect()
+}
+
+/* Folds every element to ASCII and removes the duplicates this creates, e.g. "sää" and "saa".
+   Returns the number of removed elements, so the list size and bits per element stay honest.
+*/
+pub fn fold_elements(elements: &mut Vec<String>) -> usize {
+    let before = elements.len();
+    for element in elements.iter_mut() {
+        *element = ascii_fold(element);
+    }
+    dedup_elements(elements);
+    before - elements.len()
+}
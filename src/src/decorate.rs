@@ -0,0 +1,114 @@
+/* decorate.rs
+
+   Passphrase decorations: one capitalized word, one digit or symbol inserted at a word boundary,
+   and separators picked from a set. Every choice is made with the unbiased sampler and adds
+   its bits to the total, so fewer words are needed to reach --bits.
+
+   The bits are exact only if a decorated passphrase can be taken apart again, so the words
+   may not contain upper case letters, the inserted characters or the separators.
+*/
+
+use zeroize::Zeroize;
+
+use crate::random;
+
+const INSERT_DIGITS: &str = "0123456789";
+const INSERT_SYMBOLS: &str = "!#$%&*+-=?@_";
+
+pub struct Decorations {
+    pub capitalize: bool,
+    pub insert: Vec<char>,
+    pub delimiters: Vec<char>,
+}
+
+/* Characters for --insert: digit, symbol or any */
+pub fn insert_chars(kind: &str) -> Vec<char> {
+    match kind {
+        "digit" => INSERT_DIGITS.chars().collect(),
+        "symbol" => INSERT_SYMBOLS.chars().collect(),
+        _ => INSERT_DIGITS.chars().chain(INSERT_SYMBOLS.chars()).collect(),
+    }
+}
+
+impl Decorations {
+    pub fn is_empty(&self) -> bool {
+        !self.capitalize && self.insert.is_empty() && self.delimiters.is_empty()
+    }
+
+    /* Checks that the decorations can not be confused with the words themselves */
+    pub fn check(&self, elements: &[String]) -> Result<(), String> {
+        if self.capitalize {
+            if elements.iter().any(|element| element.chars().any(|c| c.is_uppercase())) {
+                return Err("--capitalize needs an alphabet without upper case letters".to_string());
+            }
+            if let Some(element) = elements.iter().find(|element| !element.chars().next().is_some_and(|c| c.is_lowercase())) {
+                return Err(format!("--capitalize needs every element to start with a lower case letter, \"{}\" does not", element));
+            }
+        }
+        if let Some(c) = self.insert.iter().find(|c| elements.iter().any(|element| element.contains(**c))) {
+            return Err(format!("--insert character '{}' is part of the alphabet", c));
+        }
+        if let Some(c) = self.delimiters.iter().find(|c| elements.iter().any(|element| element.contains(**c))) {
+            return Err(format!("--delimiter-set character '{}' is part of the alphabet", c));
+        }
+        Ok(())
+    }
+
+    /* Bits added to a passphrase of num_elements words */
+    pub fn bits(&self, num_elements: usize) -> f64 {
+        let mut bits = 0.0;
+        if self.capitalize {
+            bits += (num_elements as f64).log2();
+        }
+        if !self.insert.is_empty() {
+            bits += ((num_elements + 1) as f64).log2() + (self.insert.len() as f64).log2();
+        }
+        if !self.delimiters.is_empty() {
+            bits += num_elements.saturating_sub(1) as f64 * (self.delimiters.len() as f64).log2();
+        }
+        bits
+    }
+
+    /* Joins the words with the decorations. The inserted character goes before word i, or at the end. */
+    pub fn apply(&self, elements: &[String], indices: &[usize], delimiter: &str) -> String {
+        let n = indices.len();
+        let capitalized = if self.capitalize { Some(random::generate_index(n)) } else { None };
+        let inserted = if self.insert.is_empty() {
+            None
+        } else {
+            Some((random::generate_index(n + 1), self.insert[random::generate_index(self.insert.len())]))
+        };
+
+        let mut password_string = String::new();
+        for (i, &index) in indices.iter().enumerate() {
+            if i > 0 {
+                if self.delimiters.is_empty() {
+                    password_string.push_str(delimiter);
+                } else {
+                    password_string.push(self.delimiters[random::generate_index(self.delimiters.len())]);
+                }
+            }
+            if let Some((position, c)) = inserted {
+                if position == i {
+                    password_string.push(c);
+                }
+            }
+
+            if capitalized == Some(i) {
+                let mut chars = elements[index].chars();
+                let mut word: String = chars.next().unwrap().to_uppercase().chain(chars).collect();
+                password_string.push_str(&word);
+                word.zeroize();
+            } else {
+                password_string.push_str(&elements[index]);
+            }
+        }
+        if let Some((position, c)) = inserted {
+            if position == n {
+                password_string.push(c);
+            }
+        }
+
+        password_string
+    }
+}
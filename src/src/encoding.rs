@@ -0,0 +1,98 @@
+/* encoding.rs
+
+   Exact-bit output for the encoding alphabets whose size is a power of two.
+   Instead of sampling each symbol separately, the requested amount of bytes is pulled
+   from the combined generator and encoded as a bit stream, most significant bit first,
+   the same way as RFC 4648 does. 128 bits is then exactly 32 hex digits.
+*/
+
+use crate::alphabet;
+
+const BASE64_SYMBOLS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/* Crockford base32 check symbols: the 32 data symbols followed by *~$=U */
+const CROCKFORD_CHECK_SYMBOLS: &[u8] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ*~$=U";
+
+/* Bits per symbol for the alphabets that support exact-bit output */
+pub fn exact_bits_per_symbol(alphabet_name: &str) -> Option<u32> {
+    match alphabet_name {
+        "hex" => Some(4),
+        "base32" | "crockford32" | "zbase32" => Some(5),
+        "base64url" => Some(6),
+        _ => None,
+    }
+}
+
+/* Padding character and block size in symbols, for the encodings that define padding */
+fn padding(alphabet_name: &str) -> Option<(char, usize)> {
+    match alphabet_name {
+        "base32" => Some(('=', 8)),
+        "base64url" => Some(('=', 4)),
+        _ => None,
+    }
+}
+
+pub fn supports_padding(alphabet_name: &str) -> bool {
+    padding(alphabet_name).is_some()
+}
+
+
+/* Encodes the bytes with the named alphabet. The last symbol is filled with zero bits. */
+pub fn encode(bytes: &[u8], alphabet_name: &str, pad: bool) -> String {
+    let bits_per_symbol = exact_bits_per_symbol(alphabet_name).expect("Not an exact-bit alphabet");
+    let (_, alphabet_item) = alphabet::alphabet_get_functions(alphabet_name).unwrap();
+
+    let mut output = encode_bitstream(bytes, bits_per_symbol, |index| alphabet_item(index).unwrap());
+
+    if pad {
+        if let Some((pad_char, block)) = padding(alphabet_name) {
+            while !output.len().is_multiple_of(block) {
+                output.push(pad_char);
+            }
+        }
+    }
+
+    output
+}
+
+/* Standard RFC 4648 base64 with padding */
+pub fn encode_base64(bytes: &[u8]) -> String {
+    let mut output = encode_bitstream(bytes, 6, |index| (BASE64_SYMBOLS[index] as char).to_string());
+    while !output.len().is_multiple_of(4) {
+        output.push('=');
+    }
+    output
+}
+
+fn encode_bitstream<F: Fn(usize) -> String>(bytes: &[u8], bits_per_symbol: u32, symbol: F) -> String {
+    let mut output = String::new();
+    let mut buffer: u32 = 0;
+    let mut buffered_bits: u32 = 0;
+
+    for &byte in bytes {
+        buffer = (buffer << 8) | byte as u32;
+        buffered_bits += 8;
+        while buffered_bits >= bits_per_symbol {
+            buffered_bits -= bits_per_symbol;
+            let index = (buffer >> buffered_bits) & ((1 << bits_per_symbol) - 1);
+            output.push_str(&symbol(index as usize));
+        }
+        buffer &= (1 << buffered_bits) - 1;
+    }
+    if buffered_bits > 0 {
+        let index = (buffer << (bits_per_symbol - buffered_bits)) & ((1 << bits_per_symbol) - 1);
+        output.push_str(&symbol(index as usize));
+    }
+
+    output
+}
+
+/* Crockford check symbol: the encoded value modulo 37 */
+pub fn crockford_check_symbol(encoded: &str) -> char {
+    let mut remainder: u32 = 0;
+    for c in encoded.bytes() {
+        let value = CROCKFORD_CHECK_SYMBOLS[..32].iter().position(|&s| s == c).expect("Not a Crockford base32 symbol");
+        remainder = (remainder * 32 + value as u32) % 37;
+    }
+    CROCKFORD_CHECK_SYMBOLS[remainder as usize] as char
+}
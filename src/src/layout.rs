@@ -0,0 +1,110 @@
+/* layout.rs
+
+   Keyboard layout definitions for computing characters that type the same on several layouts.
+   This matters for BIOS, pre-boot disk encryption prompts and HSM consoles, where the active layout is unknown.
+
+   Each layout lists the characters of the alphanumeric block, without and with shift, row by row:
+   E00..E12 (number row), D01..D12, C01..C12 (C12 is the ISO key next to Enter, backslash on US)
+   and B00..B10 (B00 is the ISO key next to left shift). A space marks a dead key or a key that
+   does not exist on the layout. AltGr levels are not used, as they are missing from many consoles.
+*/
+
+pub struct Layout {
+    pub name: &'static str,
+    pub description: &'static str,
+    rows: [&'static str; 4],
+    shift_rows: [&'static str; 4],
+}
+
+pub static LAYOUTS: &[Layout] = &[
+    Layout {
+        name: "us",
+        description: "US QWERTY",
+        rows: ["`1234567890-=", "qwertyuiop[]", "asdfghjkl;'\\", " zxcvbnm,./"],
+        shift_rows: ["~!@#$%^&*()_+", "QWERTYUIOP{}", "ASDFGHJKL:\"|", " ZXCVBNM<>?"],
+    },
+    Layout {
+        name: "fi",
+        description: "Finnish / Swedish QWERTY",
+        rows: ["§1234567890+ ", "qwertyuiopå ", "asdfghjklöä'", "<zxcvbnm,.-"],
+        shift_rows: ["½!\"#¤%&/()=? ", "QWERTYUIOPÅ ", "ASDFGHJKLÖÄ*", ">ZXCVBNM;:_"],
+    },
+    Layout {
+        name: "de",
+        description: "German QWERTZ",
+        rows: [" 1234567890ß ", "qwertzuiopü+", "asdfghjklöä#", "<yxcvbnm,.-"],
+        shift_rows: ["°!\"§$%&/()=? ", "QWERTZUIOPÜ*", "ASDFGHJKLÖÄ'", ">YXCVBNM;:_"],
+    },
+    Layout {
+        name: "fr",
+        description: "French AZERTY",
+        rows: ["²&é\"'(-è_çà)=", "azertyuiop $", "qsdfghjklmù*", "<wxcvbn,;:!"],
+        shift_rows: [" 1234567890°+", "AZERTYUIOP £", "QSDFGHJKLM%µ", ">WXCVBN?./§"],
+    },
+    Layout {
+        name: "uk",
+        description: "UK QWERTY",
+        rows: ["`1234567890-=", "qwertyuiop[]", "asdfghjkl;'#", "\\zxcvbnm,./"],
+        shift_rows: ["¬!\"£$%^&*()_+", "QWERTYUIOP{}", "ASDFGHJKL:@~", "|ZXCVBNM<>?"],
+    },
+];
+
+pub fn get_layout(name: &str) -> Option<&'static Layout> {
+    LAYOUTS.iter().find(|layout| layout.name == name)
+}
+
+/* "us (US QWERTY), fi (...)" for error messages */
+pub fn layout_descriptions() -> String {
+    LAYOUTS.iter().map(|layout| format!("{} ({})", layout.name, layout.description)).collect::<Vec<_>>().join(", ")
+}
+
+impl Layout {
+    /* Unshifted characters of each row, a space for dead and missing keys */
+    pub fn rows(&self) -> &[&'static str; 4] {
+        &self.rows
+    }
+
+    /* Characters at each (row, key, shift) position, None for dead and missing keys */
+    fn positions(&self) -> Vec<Option<char>> {
+        let mut positions = Vec::new();
+        for rows in [&self.rows, &self.shift_rows] {
+            for row in rows.iter() {
+                positions.extend(row.chars().map(|c| if c == ' ' { None } else { Some(c) }));
+            }
+        }
+        positions
+    }
+}
+
+
+/* Parses a comma separated list of layout names */
+pub fn parse_layouts(spec: &str) -> Result<Vec<&'static Layout>, String> {
+    let mut layouts: Vec<&'static Layout> = Vec::new();
+    for name in spec.split(',').map(|name| name.trim()).filter(|name| !name.is_empty()) {
+        let layout = get_layout(name)
+            .ok_or_else(|| format!("unknown layout \"{}\", use one of {}", name, layout_descriptions()))?;
+        if !layouts.iter().any(|l| l.name == layout.name) {
+            layouts.push(layout);
+        }
+    }
+    if layouts.is_empty() {
+        return Err("no layouts given".to_string());
+    }
+    Ok(layouts)
+}
+
+/* Characters produced by the same key and shift state on every layout, in key order */
+pub fn common_characters(layouts: &[&Layout]) -> Vec<char> {
+    let all_positions: Vec<Vec<Option<char>>> = layouts.iter().map(|layout| layout.positions()).collect();
+    let mut result: Vec<char> = Vec::new();
+
+    for (i, first) in all_positions[0].iter().enumerate() {
+        if let Some(c) = first {
+            if all_positions.iter().all(|positions| positions[i] == Some(*c)) && !result.contains(c) {
+                result.push(*c);
+            }
+        }
+    }
+
+    result
+}
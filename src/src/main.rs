@@ -5,13 +5,40 @@
 use random::{generate_u64_os, generate_u64_rdrand, generate_u64_cpujitter, generate_u64};
 
 #[path = "alphabet.rs"] mod alphabet;
+#[path = "bench.rs"] mod bench;
+#[path = "charset.rs"] mod charset;
+#[path = "wordlist.rs"] mod wordlist;
+#[path = "bip39.rs"] mod bip39;
+#[path = "encoding.rs"] mod encoding;
+#[path = "secure_file.rs"] mod secure_file;
+#[path = "policy.rs"] mod policy;
+#[path = "passwordrules.rs"] mod passwordrules;
+#[path = "mask.rs"] mod mask;
+#[path = "layout.rs"] mod layout;
+#[path = "normalize.rs"] mod normalize;
+#[path = "decorate.rs"] mod decorate;
+#[path = "patterns.rs"] mod patterns;
+#[path = "alphabet_info.rs"] mod alphabet_info;
+#[path = "strength.rs"] mod strength;
+#[path = "breach.rs"] mod breach;
+#[path = "output.rs"] mod output;
+#[path = "qr.rs"] mod qr;
+#[path = "regex.rs"] mod regex;
+#[path = "bip39_wordlists.rs"] mod bip39_wordlists;
+#[path = "eff_wordlists.rs"] mod eff_wordlists;
 use alphabet::{alphabet_commonsafe_get_element, alphabet_commonsafe_get_count, alphabet_normal_get_element, alphabet_normal_get_count, alphabet_ascii_get_element, alphabet_ascii_get_count, alphabet_assembly_get_element, alphabet_assembly_get_count};
 use zeroize::Zeroize;
+use num_traits::Zero;
 
+use std::collections::BTreeMap;
 use std::env;
 use std::fmt;
+use std::io::{IsTerminal, Write};
 use std::str::FromStr;
-use clap::{App, Arg};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use clap::{App, Arg, SubCommand};
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 const PACKAGE_NAME: &str = env!("CARGO_PKG_NAME");
@@ -54,8 +81,41 @@ struct Config {
     debug: bool,
     bits: u32,
     alphabet: String,
+    charset: Option<String>,
+    layouts: Option<String>,
+    wordlist: Option<String>,
+    dice: bool,
+    pad: bool,
+    require: Option<String>,
+    rules: Option<String>,
+    mask: Option<String>,
+    custom_charsets: [Option<String>; 4],
+    check_symbol: bool,
+    include: Option<String>,
+    fold_ascii: bool,
+    normalize: normalize::NormalForm,
+    exclude: Option<String>,
     delimiter: String,
+    delimiter_set: Option<String>,
+    capitalize: bool,
+    unique: bool,
+    no_patterns: Option<String>,
+    insert: Option<String>,
+    breach_db: Option<String>,
+    output: output::OutputFormat,
+    out: Option<String>,
+    split_out: Option<String>,
+    name_template: String,
+    qr: bool,
+    qr_file: Option<String>,
+    qr_ec: Option<String>,
+    qr_payload: Option<String>,
+    wifi_ssid: Option<String>,
+    otp_label: Option<String>,
+    otp_issuer: Option<String>,
     count: usize,
+    threads: usize,
+    syllables: usize,
     rngtest: Option<(RandomSource, u32, NumFormat)>,
 }
 
@@ -84,12 +144,11 @@ impl FromStr for NumFormat {
 fn print_formatted_value(value: u64, mode: NumFormat) {
     match mode {
         NumFormat::RawBinary => {
-            
-            for shift in (0..=56).step_by(8) {
-                let byte = ((value >> shift) & 0xFF) as u8;
-                print!("{}", byte as char);
+            // Write the bytes as such. Printing them as chars would UTF-8 encode every byte above 0x7f.
+            let bytes: [u8; 8] = value.to_le_bytes();
+            if std::io::stdout().write_all(&bytes).is_err() {
+                std::process::exit(1);
             }
-            
         }
         NumFormat::U8 => {
             let bytes: [u8; 8] = value.to_be_bytes();
@@ -128,6 +187,567 @@ fn print_formatted_value(value: u64, mode: NumFormat) {
     }
 }
 
+/* Number of alphabet elements needed to reach the requested amount of bits */
+fn elements_for_bits(bits: u32, alphabet_count: usize) -> u32 {
+    let bits_per_element = (alphabet_count as f64).log2();
+    (bits as f64 / bits_per_element).ceil() as u32
+}
+
+/* Samples used to estimate the fraction of passwords rejected by --no-patterns */
+const PATTERN_SAMPLES: usize = 10000;
+
+/* Picks num_elements random indexes into an alphabet of the given size, each one separately with the combined generator */
+fn generate_indices(alphabet_count: usize, num_elements: u32) -> Vec<usize> {
+    (0..num_elements).map(|_| random::generate_index(alphabet_count)).collect()
+}
+
+/* Picks num_elements distinct indexes. An index already picked is drawn again, which keeps every
+   ordered selection of distinct elements equally likely.
+*/
+fn generate_unique_indices(alphabet_count: usize, num_elements: u32) -> Vec<usize> {
+    let mut indices: Vec<usize> = Vec::with_capacity(num_elements as usize);
+    while indices.len() < num_elements as usize {
+        let index = random::generate_index(alphabet_count);
+        if !indices.contains(&index) {
+            indices.push(index);
+        }
+    }
+    indices
+}
+
+/* Creates a single password by picking each element separately with the combined generator */
+fn generate_password(elements: &[String], num_elements: u32, delimiter: &str) -> String {
+    let indices = generate_indices(elements.len(), num_elements);
+    join_elements(elements, &indices, delimiter)
+}
+
+fn join_elements(elements: &[String], indices: &[usize], delimiter: &str) -> String {
+    join_grouped(elements, indices, delimiter, 1)
+}
+
+/* Joins the elements, putting the delimiter after every group_size elements */
+fn join_grouped(elements: &[String], indices: &[usize], delimiter: &str, group_size: usize) -> String {
+    let mut password_string = String::new();
+
+    for (i, &index) in indices.iter().enumerate() {
+        password_string.push_str(&elements[index]);
+        if i < indices.len() - 1 && (i + 1) % group_size == 0 {
+            password_string.push_str(delimiter);
+        }
+    }
+
+    password_string
+}
+
+fn satisfies_rules(rules: &Option<passwordrules::PasswordRules>, elements: &[String], indices: &[usize]) -> bool {
+    match rules {
+        Some(rules) => {
+            let password: Vec<char> = indices.iter().map(|&index| elements[index].chars().next().unwrap()).collect();
+            rules.satisfies(&password)
+        }
+        None => true,
+    }
+}
+
+/* Creates count passwords using the given amount of threads and prints them in order.
+   Worker threads pick the next free index, and the results are printed as soon as all
+   passwords before them are ready. With a single thread everything runs on the calling thread.
+*/
+fn generate_batch<F>(count: usize, threads: usize, output: &output::Output, generate: F)
+where
+    F: Fn(usize) -> String + Sync,
+{
+    if threads <= 1 || count <= 1 {
+        for i in 0..count {
+            let mut password_string = generate(i);
+            write_or_exit(output, i, &password_string);
+            password_string.zeroize();
+        }
+        finish_or_exit(output);
+        return;
+    }
+
+    let next_index = AtomicUsize::new(0);
+    let (sender, receiver) = mpsc::channel::<(usize, String)>();
+
+    thread::scope(|scope| {
+        for _ in 0..threads.min(count) {
+            let sender = sender.clone();
+            let next_index = &next_index;
+            let generate = &generate;
+            scope.spawn(move || loop {
+                let i = next_index.fetch_add(1, Ordering::Relaxed);
+                if i >= count || sender.send((i, generate(i))).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(sender);
+
+        // Reorder the results so that the output does not depend on thread scheduling
+        let mut pending: BTreeMap<usize, String> = BTreeMap::new();
+        let mut next_to_print = 0;
+        for (i, password_string) in receiver {
+            pending.insert(i, password_string);
+            while let Some(mut password_string) = pending.remove(&next_to_print) {
+                write_or_exit(output, next_to_print, &password_string);
+                password_string.zeroize();
+                next_to_print += 1;
+            }
+        }
+    });
+    finish_or_exit(output);
+}
+
+fn write_or_exit(output: &output::Output, index: usize, password_string: &str) {
+    if let Err(e) = output.write(index, password_string) {
+        eprintln!("Error: {}. Exiting.", e);
+        std::process::exit(1);
+    }
+}
+
+fn finish_or_exit(output: &output::Output) {
+    if let Err(e) = output.finish() {
+        eprintln!("Error: {}. Exiting.", e);
+        std::process::exit(1);
+    }
+}
+
+/* QR code output for --qr and --qr-file, None without them. Called before destination_or_exit(),
+   so that a wrong QR option fails before --split-out creates its directory.
+*/
+fn qr_output_or_exit(config: &Config) -> Option<output::QrOutput> {
+    // The QR options do nothing without --qr or --qr-file, and the payload options only with their payload
+    let payload_kind = config.qr_payload.as_deref().unwrap_or("text");
+    let qr_options = [
+        ("qr-ec", config.qr_ec.is_some(), None),
+        ("qr-payload", config.qr_payload.is_some(), None),
+        ("wifi-ssid", config.wifi_ssid.is_some(), Some("wifi")),
+        ("otp-label", config.otp_label.is_some(), Some("otpauth")),
+        ("otp-issuer", config.otp_issuer.is_some(), Some("otpauth")),
+    ];
+    for (option, given, kind) in qr_options {
+        if given && !config.qr && config.qr_file.is_none() {
+            eprintln!("Error: --{} needs --qr or --qr-file. Exiting.", option);
+            std::process::exit(1);
+        }
+        if let Some(kind) = kind.filter(|&kind| given && kind != payload_kind) {
+            eprintln!("Error: --{} only applies to --qr-payload {}. Exiting.", option, kind);
+            std::process::exit(1);
+        }
+    }
+    if !config.qr && config.qr_file.is_none() {
+        return None;
+    }
+    if config.qr && config.output == output::OutputFormat::Json && config.out.is_none() && config.split_out.is_none() {
+        eprintln!("Error: --qr would mix the QR codes into the JSON on stdout, use --out or --split-out for the JSON. Exiting.");
+        std::process::exit(1);
+    }
+
+    let payload = match payload_kind {
+        "wifi" => match &config.wifi_ssid {
+            Some(ssid) => qr::Payload::Wifi { ssid: ssid.clone() },
+            None => {
+                eprintln!("Error: --qr-payload wifi needs --wifi-ssid. Exiting.");
+                std::process::exit(1);
+            }
+        },
+        "otpauth" => match &config.otp_label {
+            Some(label) => qr::Payload::Otpauth { label: label.clone(), issuer: config.otp_issuer.clone() },
+            None => {
+                eprintln!("Error: --qr-payload otpauth needs --otp-label. Exiting.");
+                std::process::exit(1);
+            }
+        },
+        _ => qr::Payload::Text,
+    };
+    let ec = qr::ErrorCorrection::from_str(config.qr_ec.as_deref().unwrap_or("M")).expect("Invalid error correction level");
+
+    match output::QrOutput::new(ec, payload, config.qr, config.qr_file.as_deref(), config.count, config.split_out.is_some()) {
+        Ok(qr_output) => Some(qr_output),
+        Err(e) => {
+            eprintln!("Error: {}. Exiting.", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/* Where the secrets go: stdout, a new --out file or a new file per secret under --split-out */
+fn destination_or_exit(config: &Config) -> output::Destination {
+    let destination = match (&config.out, &config.split_out) {
+        (Some(path), _) => output::Destination::file(path),
+        (None, Some(dir)) => output::Destination::split(dir, &config.name_template, config.count),
+        (None, None) => Ok(output::Destination::Stdout),
+    };
+    match destination {
+        Ok(destination) => destination,
+        Err(e) => {
+            eprintln!("Error: {}. Exiting.", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/* keygen key: raw key bytes from the combined generator, to stdout or a new file */
+fn run_key(matches: &clap::ArgMatches) -> ! {
+    let num_bytes = match matches.value_of("bytes").unwrap().parse::<usize>() {
+        Ok(n) if n > 0 => n,
+        _ => {
+            eprintln!("Error: --bytes must be a positive number. Exiting.");
+            std::process::exit(1);
+        }
+    };
+
+    let mut key = random::generate_bytes(num_bytes);
+    let mut encoded = match matches.value_of("encoding").unwrap() {
+        "hex" => Some(hex::encode(&key)),
+        "base64" => Some(encoding::encode_base64(&key)),
+        _ => None,
+    };
+    // The encoded key is copied into a buffer of the final size, so no reallocation leaves a copy behind
+    let mut output: Vec<u8> = match &encoded {
+        Some(text) => {
+            let mut line = Vec::with_capacity(text.len() + 1);
+            line.extend_from_slice(text.as_bytes());
+            line.push(b'\n');
+            line
+        }
+        None => key.clone(),
+    };
+    if let Some(text) = &mut encoded {
+        text.zeroize();
+    }
+    key.zeroize();
+
+    let result = match matches.value_of("out") {
+        Some(path) => secure_file::write_secret_file(path, &output),
+        None => std::io::stdout().write_all(&output).map_err(|e| format!("Failed to write the key: {}", e)),
+    };
+    output.zeroize();
+
+    if let Err(e) = result {
+        eprintln!("Error: {}. Exiting.", e);
+        std::process::exit(1);
+    }
+
+    std::process::exit(0);
+}
+
+/* Reads a secret without echo from the terminal, or one line from stdin */
+fn read_secret_or_exit() -> String {
+    let read = if std::io::stdin().is_terminal() {
+        rpassword::prompt_password("Password: ").map_err(|e| e.to_string())
+    } else {
+        let mut line = String::new();
+        std::io::stdin().read_line(&mut line).map(|_| line).map_err(|e| e.to_string())
+    };
+    let mut secret = match read {
+        Ok(secret) => secret,
+        Err(e) => {
+            eprintln!("Error: Failed to read the password: {}. Exiting.", e);
+            std::process::exit(1);
+        }
+    };
+    while secret.ends_with('\n') || secret.ends_with('\r') {
+        secret.pop();
+    }
+    if secret.is_empty() {
+        eprintln!("Error: Empty password. Exiting.");
+        std::process::exit(1);
+    }
+    secret
+}
+
+/* keygen check: estimate the strength of an existing secret, read without echo or from stdin */
+fn run_check() -> ! {
+    let mut secret = read_secret_or_exit();
+    strength::print_estimates(&secret);
+    secret.zeroize();
+    std::process::exit(0);
+}
+
+fn open_breach_db_or_exit(path: &str) -> breach::BreachDb {
+    match breach::open(path) {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("Error: {}. Exiting.", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/* The options behind the generated secrets, as a JSON object for --output json */
+fn source_json(config: &Config) -> String {
+    let mut fields: Vec<(&str, String)> = vec![("bits", config.bits.to_string())];
+    let built_in = config.charset.is_none() && config.layouts.is_none() && config.wordlist.is_none() && config.rules.is_none() && config.mask.is_none();
+    let options = [
+        ("alphabet", Some(&config.alphabet).filter(|_| built_in)),
+        ("charset", config.charset.as_ref()),
+        ("layouts", config.layouts.as_ref()),
+        ("wordlist", config.wordlist.as_ref()),
+        ("require", config.require.as_ref()),
+        ("rules", config.rules.as_ref()),
+        ("mask", config.mask.as_ref()),
+        ("include", config.include.as_ref()),
+        ("exclude", config.exclude.as_ref()),
+        ("delimiter_set", config.delimiter_set.as_ref()),
+        ("insert", config.insert.as_ref()),
+        ("no_patterns", config.no_patterns.as_ref()),
+    ];
+    for (name, value) in options {
+        if let Some(value) = value {
+            fields.push((name, output::json_string(value)));
+        }
+    }
+    let flags = [
+        ("fold_ascii", config.fold_ascii),
+        ("unique", config.unique),
+        ("capitalize", config.capitalize),
+        ("pad", config.pad),
+        ("check_symbol", config.check_symbol),
+        ("breach_check", config.breach_db.is_some()),
+    ];
+    for (name, value) in flags {
+        if value {
+            fields.push((name, "true".to_string()));
+        }
+    }
+    fields.push(("normalize", output::json_string(config.normalize.name())));
+    output::json_object(&fields)
+}
+
+/* Whether a generated password is in the breach list. Hits are reported without the password. */
+fn is_breached(breach_db: &Option<breach::BreachDb>, password: &str) -> bool {
+    match breach_db.as_ref().and_then(|db| db.lookup(password)) {
+        Some(count) => {
+            eprintln!("Warning: A generated password was found in the breach list {} times, regenerating.", count);
+            true
+        }
+        None => false,
+    }
+}
+
+/* keygen breach-check: look up a secret in a local HIBP SHA-1 list, or build the index of the list.
+   Exits with 0 if the secret is not in the list and 2 if it is.
+*/
+fn run_breach_check(matches: &clap::ArgMatches) -> ! {
+    let path = matches.value_of("db").unwrap();
+
+    if matches.is_present("build-index") {
+        match breach::build_index(path) {
+            Ok(lines) => {
+                println!("Indexed {} hashes into {}.idx", lines, path);
+                std::process::exit(0);
+            }
+            Err(e) => {
+                eprintln!("Error: {}. Exiting.", e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    let db = open_breach_db_or_exit(path);
+    let mut secret = read_secret_or_exit();
+    let count = db.lookup(&secret);
+    secret.zeroize();
+
+    match count {
+        Some(count) => {
+            println!("Found in the breach list: {} occurrences", count);
+            std::process::exit(2);
+        }
+        None => {
+            println!("Not found in the breach list");
+            std::process::exit(0);
+        }
+    }
+}
+
+/* keygen shred: overwrite and remove secret files */
+fn run_shred(matches: &clap::ArgMatches) -> ! {
+    let passes = match matches.value_of("passes").unwrap().parse::<usize>() {
+        Ok(n) if n > 0 => n,
+        _ => {
+            eprintln!("Error: --passes must be a positive number. Exiting.");
+            std::process::exit(1);
+        }
+    };
+
+    let mut failed = false;
+    for path in matches.values_of("FILE").unwrap() {
+        if let Some(reason) = secure_file::overwrite_warning(path) {
+            eprintln!("Warning: {}: {}, the old data may survive the overwrite.", path, reason);
+        }
+        match secure_file::shred_file(path, passes) {
+            Ok(()) => eprintln!("Shredded {}", path),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                failed = true;
+            }
+        }
+    }
+
+    std::process::exit(if failed { 1 } else { 0 });
+}
+
+/* keygen regex: generate strings uniformly from a bounded regular expression */
+fn run_regex(matches: &clap::ArgMatches, config: Config) -> ! {
+    let pattern = matches.value_of("PATTERN").unwrap();
+    let generator = match regex::compile(pattern) {
+        Ok(generator) => generator,
+        Err(e) => {
+            eprintln!("Error: Invalid expression: {}. Exiting.", e);
+            std::process::exit(1);
+        }
+    };
+    let bits = policy::log2_big(generator.language_size());
+
+    if matches.is_present("check") {
+        println!("Expression: {}", pattern);
+        for (length, count) in generator.length_counts().iter().enumerate() {
+            if !count.is_zero() {
+                println!("  length {:>3}: {} strings, {:.4} bits", length, count, policy::log2_big(count));
+            }
+        }
+        println!("Total: {} strings", generator.language_size());
+        println!("Entropy: {:.4} bits", bits);
+        std::process::exit(0);
+    }
+
+    let config = Config { count: matches.value_of("count").unwrap().parse::<usize>().unwrap_or(1).max(1), ..config };
+    eprintln!("Regex entropy: {:.4} bits", bits);
+
+    let output = output::Output {
+        format: config.output,
+        alphabet: "regex".to_string(),
+        alphabet_size: None,
+        entropy_bits: bits,
+        elements: None,
+        delimiter: String::new(),
+        source: output::json_object(&[("pattern", output::json_string(pattern))]),
+        qr: qr_output_or_exit(&config),
+        destination: destination_or_exit(&config),
+    };
+    generate_batch(config.count, config.threads, &output, |_| generator.generate());
+
+    std::process::exit(0);
+}
+
+/* keygen bip39: generate or validate a mnemonic, print the seed if asked */
+fn run_bip39(matches: &clap::ArgMatches, config: Config) -> ! {
+    let passphrase = matches.value_of("passphrase").unwrap_or("");
+
+    // Only a generated mnemonic is a secret of its own, the seed and validation go to stdout as text
+    let validate_matches = matches.subcommand_matches("validate");
+    let redirected = config.output != output::OutputFormat::Text || config.out.is_some() || config.split_out.is_some() || config.qr || config.qr_file.is_some();
+    if redirected && (validate_matches.is_some() || matches.is_present("seed")) {
+        eprintln!("Error: --output, --out, --split-out, --qr and --qr-file only apply to generating a mnemonic without --seed. Exiting.");
+        std::process::exit(1);
+    }
+
+    let mut mnemonic = if let Some(validate_matches) = validate_matches {
+        let mnemonic = validate_matches.values_of("MNEMONIC").unwrap().collect::<Vec<&str>>().join(" ");
+        match bip39::validate_mnemonic(&mnemonic, matches.value_of("lang")) {
+            // --words has a default, so it is only checked when given
+            Ok(validation) if matches.occurrences_of("words") > 0 && matches.value_of("words").unwrap().parse::<usize>() != Ok(validation.num_words) => {
+                eprintln!("Invalid: mnemonic has {} words, --words asks for {}", validation.num_words, matches.value_of("words").unwrap());
+                std::process::exit(1);
+            }
+            Ok(validation) => {
+                println!("Valid: {} words, {} bits of entropy, language {}", validation.num_words, validation.entropy_bits, validation.lang);
+                mnemonic
+            }
+            Err(e) => {
+                eprintln!("Invalid: {}", e);
+                std::process::exit(1);
+            }
+        }
+    } else {
+        let num_words = matches.value_of("words").unwrap().parse::<usize>().unwrap();
+        let lang = matches.value_of("lang").unwrap_or("en");
+        let mnemonic = match bip39::generate_mnemonic(num_words, lang) {
+            Ok(mnemonic) => mnemonic,
+            Err(e) => {
+                eprintln!("Error: {}. Exiting.", e);
+                std::process::exit(1);
+            }
+        };
+
+        let config = Config { count: 1, ..config };
+        let output = output::Output {
+            format: config.output,
+            alphabet: format!("bip39-{}", lang),
+            alphabet_size: Some(2048),
+            // ENT = 32 * words / 3 bits, the checksum adds none
+            entropy_bits: (num_words * 32 / 3) as f64,
+            elements: Some(num_words),
+            delimiter: bip39::word_separator(lang).to_string(),
+            source: output::json_object(&[("words", num_words.to_string()), ("lang", output::json_string(lang))]),
+            qr: qr_output_or_exit(&config),
+            destination: destination_or_exit(&config),
+        };
+        write_or_exit(&output, 0, &mnemonic);
+        finish_or_exit(&output);
+        mnemonic
+    };
+
+    if matches.is_present("seed") {
+        let mut seed = bip39::mnemonic_to_seed(&mnemonic, passphrase);
+        println!("{}", hex::encode(seed));
+        seed.zeroize();
+    }
+    mnemonic.zeroize();
+
+    std::process::exit(0);
+}
+
+/* Custom charset options -1 .. -4 for masks */
+fn mask_charset_args() -> Vec<Arg<'static, 'static>> {
+    ["1", "2", "3", "4"].iter().map(|n| {
+        Arg::with_name(n)
+            .short(n)
+            .long(match *n { "1" => "custom-charset1", "2" => "custom-charset2", "3" => "custom-charset3", _ => "custom-charset4" })
+            .value_name("CHARSET")
+            .help("Custom charset for ?1 .. ?4 in a mask")
+    }).collect()
+}
+
+fn custom_charsets(matches: &clap::ArgMatches) -> [Option<String>; 4] {
+    ["1", "2", "3", "4"].map(|n| matches.value_of(n).map(|c| c.to_string()))
+}
+
+fn parse_mask_or_exit(mask: &str, custom: &[Option<String>; 4]) -> Vec<Vec<char>> {
+    match mask::parse_mask(mask, custom) {
+        Ok(positions) => positions,
+        Err(e) => {
+            eprintln!("Error: Invalid mask: {}. Exiting.", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/* Loads a wordlist file, exits if it can not be read */
+fn load_wordlist_or_exit(path: &str) -> wordlist::WordlistReport {
+    match wordlist::load_wordlist(path) {
+        Ok(report) => report,
+        Err(e) => {
+            eprintln!("Error: {}. Exiting.", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/* Parses a charset given on the command line, exits on invalid syntax */
+fn parse_charset_or_exit(spec: &str, option_name: &str) -> Vec<char> {
+    match charset::parse_charset(spec) {
+        Ok(chars) => chars,
+        Err(e) => {
+            eprintln!("Error: Invalid --{}: {}. Exiting.", option_name, e);
+            std::process::exit(1);
+        }
+    }
+}
+
 fn main() {
     let matches = App::new(PACKAGE_NAME)
         .version(VERSION)
@@ -142,9 +762,91 @@ fn main() {
                 .short("a")
                 .long("alphabet")
                 .value_name("ALPHABET")
-                .possible_values(&["words-fi", "commonsafe", "normal", "ascii", "assembly"])
+                .possible_values(alphabet::ALPHABET_NAMES)
                 .help("Specify the alphabet to use for random value generation"),
         )
+        .arg(
+            Arg::with_name("charset")
+                .long("charset")
+                .value_name("CHARSET")
+                .conflicts_with("alphabet")
+                .help("Use a custom character set instead of an alphabet. Supports ranges (a-z0-9) and \\ escapes"),
+        )
+        .arg(
+            Arg::with_name("layouts")
+                .long("layouts")
+                .value_name("LAYOUTS")
+                .conflicts_with_all(&["alphabet", "charset"])
+                .help("Use the characters typed by the same key and shift state on all these keyboard layouts, e.g. us,fi,de. Known layouts: us, fi, de, fr, uk"),
+        )
+        .arg(
+            Arg::with_name("wordlist")
+                .long("wordlist")
+                .value_name("FILE")
+                .conflicts_with_all(&["alphabet", "charset", "layouts"])
+                .help("Use a wordlist file (UTF-8, one word per line) instead of an alphabet"),
+        )
+        .arg(
+            Arg::with_name("dice")
+                .long("dice")
+                .conflicts_with_all(&["charset", "include", "exclude"])
+                .help("Print the dice rolls that select each word. Needs a list of 6^n words, e.g. a diceware list"),
+        )
+        .arg(
+            Arg::with_name("require")
+                .long("require")
+                .value_name("CLASSES")
+                .help("Require character classes, e.g. upper,lower,digit,symbol or upper:2,digit:3. Passwords not meeting them are regenerated"),
+        )
+        .arg(
+            Arg::with_name("rules")
+                .long("rules")
+                .value_name("DESCRIPTOR")
+                .conflicts_with_all(&["alphabet", "charset", "layouts", "wordlist", "require", "include", "exclude"])
+                .help("Generate a password meeting a passwordrules descriptor, e.g. 'minlength: 20; required: lower; required: upper; max-consecutive: 2'"),
+        )
+        .arg(
+            Arg::with_name("mask")
+                .long("mask")
+                .value_name("MASK")
+                .conflicts_with_all(&["alphabet", "charset", "layouts", "wordlist", "require", "rules", "include", "exclude", "fold-ascii", "bits"])
+                .help("Generate passwords from a hashcat style mask, e.g. '?u?l?l?l-?d?d?d?d?s'"),
+        )
+        .args(&mask_charset_args())
+        .arg(
+            Arg::with_name("pad")
+                .long("pad")
+                .help("Add '=' padding to base32 and base64url output"),
+        )
+        .arg(
+            Arg::with_name("check-symbol")
+                .long("check-symbol")
+                .help("Append the Crockford check symbol to crockford32 output"),
+        )
+        .arg(
+            Arg::with_name("include")
+                .long("include")
+                .value_name("CHARSET")
+                .help("Add these characters to the alphabet"),
+        )
+        .arg(
+            Arg::with_name("normalize")
+                .long("normalize")
+                .value_name("FORM")
+                .possible_values(&["nfc", "nfd", "ascii"])
+                .help("Unicode form of the output, nfc by default. ascii drops accents, words that become equal are kept once"),
+        )
+        .arg(
+            Arg::with_name("fold-ascii")
+                .long("fold-ascii")
+                .help("Fold ä, ö, å, š and ž to ASCII and drop the duplicates this creates, e.g. for assembly or a Finnish wordlist"),
+        )
+        .arg(
+            Arg::with_name("exclude")
+                .long("exclude")
+                .value_name("CHARSET")
+                .help("Remove these characters from the alphabet. For wordlists, removes words containing them"),
+        )
         .arg(
             Arg::with_name("bits")
                 .short("b")
@@ -159,15 +861,146 @@ fn main() {
                 .value_name("COUNT")
                 .help("Number of passwords to generate"),
         )
+        .arg(
+            Arg::with_name("threads")
+                .short("t")
+                .long("threads")
+                .value_name("N")
+                .help("Number of threads used to generate the passwords. Output order is kept."),
+        )
+        .arg(
+            Arg::with_name("syllables")
+                .long("syllables")
+                .value_name("N")
+                .help("Syllables per pseudo-word with the pronounceable alphabets. The delimiter goes between words"),
+        )
         .arg(
             Arg::with_name("delimiter")
                 .short("d")
                 .long("delimiter")
                 .value_name("DELIMITER")
-                .requires_all(&["bits", "alphabet"]) 
+                .requires("bits")
                 .help("Sets the delimiter between each letter or word")
                 .takes_value(true),            
         )
+        .arg(
+            Arg::with_name("delimiter-set")
+                .long("delimiter-set")
+                .value_name("CHARSET")
+                .conflicts_with_all(&["delimiter", "rules", "require", "mask"])
+                .help("Pick each delimiter randomly from these characters, e.g. '.-_!'. Adds to the entropy"),
+        )
+        .arg(
+            Arg::with_name("no-patterns")
+                .long("no-patterns")
+                .value_name("PATTERNS")
+                .min_values(0)
+                .max_values(1)
+                .conflicts_with_all(&["rules", "require", "mask", "unique", "capitalize", "insert", "delimiter-set"])
+                .help("Regenerate passwords with weak patterns: repeat, sequence, keyboard and dictionary, with an optional minimum length, e.g. repeat:3,keyboard:4,dictionary:5. Without a value: repeat:3,sequence:4,keyboard:4"),
+        )
+        .arg(
+            Arg::with_name("unique")
+                .long("unique")
+                .conflicts_with_all(&["rules", "require", "mask"])
+                .help("Never repeat an element within a password. The entropy is computed for sampling without replacement"),
+        )
+        .arg(
+            Arg::with_name("capitalize")
+                .long("capitalize")
+                .conflicts_with_all(&["rules", "require", "mask"])
+                .help("Capitalize one randomly chosen word. Adds to the entropy"),
+        )
+        .arg(
+            Arg::with_name("insert")
+                .long("insert")
+                .value_name("KIND")
+                .possible_values(&["digit", "symbol", "any"])
+                .conflicts_with_all(&["rules", "require", "mask"])
+                .help("Insert a random digit, symbol or either at a random word boundary. Adds to the entropy"),
+        )
+        .arg(
+            Arg::with_name("output")
+                .long("output")
+                .value_name("FORMAT")
+                .possible_values(&["text", "json"])
+                .conflicts_with("dice")
+                .help("Output format, text by default. json prints one object per secret and line (NDJSON) with the entropy and settings"),
+        )
+        .arg(
+            Arg::with_name("out")
+                .short("o")
+                .long("out")
+                .value_name("FILE")
+                .conflicts_with_all(&["split-out", "rngtest"])
+                .help("Write the secrets to a new file with 0600 permissions instead of stdout. Existing files and symlinks are refused"),
+        )
+        .arg(
+            Arg::with_name("split-out")
+                .long("split-out")
+                .value_name("DIR")
+                .conflicts_with("rngtest")
+                .help("Write each secret to its own new 0600 file in DIR, created with 0700 permissions if missing"),
+        )
+        .arg(
+            Arg::with_name("name-template")
+                .long("name-template")
+                .value_name("TEMPLATE")
+                .requires("split-out")
+                // clap turns {n} in help texts into a line break, the word joiner keeps it literal
+                .help("File names for --split-out, {\u{2060}n} is the number of the secret. Default: secret-{\u{2060}n}.txt"),
+        )
+        .arg(
+            Arg::with_name("qr")
+                .long("qr")
+                .conflicts_with("rngtest")
+                .help("Show each secret as a QR code in the terminal, drawn for light text on a dark background"),
+        )
+        .arg(
+            Arg::with_name("qr-file")
+                .long("qr-file")
+                .value_name("FILE")
+                .conflicts_with("rngtest")
+                .help("Write each secret as a QR code to a new .svg or .png file with 0600 permissions. {\u{2060}n} is the number of the secret. With --split-out a name in DIR"),
+        )
+        .arg(
+            Arg::with_name("qr-ec")
+                .long("qr-ec")
+                .value_name("LEVEL")
+                .possible_values(&["L", "M", "Q", "H"])
+                .help("QR code error correction level: L 7%, M 15% (default), Q 25%, H 30%"),
+        )
+        .arg(
+            Arg::with_name("qr-payload")
+                .long("qr-payload")
+                .value_name("KIND")
+                .possible_values(&["text", "wifi", "otpauth"])
+                .help("What the QR code holds: the secret (text, default), a WPA network (needs --wifi-ssid) or an otpauth:// TOTP URI (needs --otp-label and a base32 secret)"),
+        )
+        .arg(
+            Arg::with_name("wifi-ssid")
+                .long("wifi-ssid")
+                .value_name("SSID")
+                .help("Network name for --qr-payload wifi"),
+        )
+        .arg(
+            Arg::with_name("otp-label")
+                .long("otp-label")
+                .value_name("LABEL")
+                .help("Account label for --qr-payload otpauth, e.g. 'Example:alice@example.com'"),
+        )
+        .arg(
+            Arg::with_name("otp-issuer")
+                .long("otp-issuer")
+                .value_name("ISSUER")
+                .help("Issuer for --qr-payload otpauth"),
+        )
+        .arg(
+            Arg::with_name("breach-db")
+                .long("breach-db")
+                .value_name("FILE")
+                .help("Regenerate passwords found in this local HIBP SHA-1 list, ordered by hash. See keygen breach-check"),
+        )
         .arg(
             Arg::with_name("rngtest")
                 .short("r")
@@ -198,14 +1031,338 @@ fn main() {
                 .required_if("rngtest", "generator") // Required if rngtest option is used
                 .help("Specifies the data format for RNG testing."),
         )
+        .subcommand(
+            SubCommand::with_name("bench")
+                .about("Measures throughput and latency of the random sources, the DRBG and password generation")
+                .arg(
+                    Arg::with_name("iterations")
+                        .short("i")
+                        .long("iterations")
+                        .value_name("N")
+                        .default_value("100")
+                        .help("Number of calls to measure for each benchmark"),
+                )
+                .arg(
+                    Arg::with_name("bits")
+                        .short("b")
+                        .long("bits")
+                        .value_name("BITS")
+                        .default_value("256")
+                        .help("Password strength used for the password generation benchmarks"),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .short("o")
+                        .long("output")
+                        .value_name("FORMAT")
+                        .possible_values(&["table", "json"])
+                        .default_value("table")
+                        .help("Output format for the results"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("wordlist")
+                .about("Wordlist tools")
+                .subcommand(
+                    SubCommand::with_name("check")
+                        .about("Validates a wordlist file and prints diagnostics without generating anything")
+                        .arg(
+                            Arg::with_name("FILE")
+                                .required(true)
+                                .help("Wordlist file, UTF-8, one word per line"),
+                        ),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("bip39")
+                .about("Generates a BIP-39 mnemonic with checksum, optionally with the derived seed")
+                .arg(
+                    Arg::with_name("words")
+                        .short("w")
+                        .long("words")
+                        .value_name("WORDS")
+                        .possible_values(bip39::BIP39_WORD_COUNTS)
+                        .default_value("24")
+                        .help("Number of words in the mnemonic"),
+                )
+                .arg(
+                    Arg::with_name("lang")
+                        .short("l")
+                        .long("lang")
+                        .value_name("LANG")
+                        .possible_values(bip39::BIP39_LANGUAGES)
+                        .help("Wordlist language. Default en, for validate detected from the words"),
+                )
+                .arg(
+                    Arg::with_name("seed")
+                        .long("seed")
+                        .help("Also print the 64 byte seed (PBKDF2-HMAC-SHA512) in hex"),
+                )
+                .arg(
+                    Arg::with_name("passphrase")
+                        .long("passphrase")
+                        .value_name("PASSPHRASE")
+                        .requires("seed")
+                        .help("Passphrase for the seed derivation. Note that command line arguments may be visible to other users"),
+                )
+                .subcommand(
+                    SubCommand::with_name("validate")
+                        .about("Checks the words and the checksum of an existing mnemonic")
+                        .arg(
+                            Arg::with_name("MNEMONIC")
+                                .required(true)
+                                .multiple(true)
+                                .help("The mnemonic words"),
+                        ),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("mask")
+                .about("Mask tools")
+                .subcommand(
+                    SubCommand::with_name("check")
+                        .about("Prints the exact entropy of a hashcat style mask without generating anything")
+                        .arg(
+                            Arg::with_name("MASK")
+                                .required(true)
+                                .help("The mask, e.g. '?u?l?l?l-?d?d?d?d?s'"),
+                        )
+                        .args(&mask_charset_args()),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("alphabets")
+                .about("Lists the built-in alphabets with their size, bits per symbol and character classes")
+                .subcommand(
+                    SubCommand::with_name("show")
+                        .about("Prints every symbol of an alphabet, one per line")
+                        .arg(
+                            Arg::with_name("NAME")
+                                .required(true)
+                                .possible_values(alphabet::ALPHABET_NAMES)
+                                .help("The alphabet"),
+                        ),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("check")
+                .about("Estimates the strength of an existing password or passphrase, read without echo or from stdin"),
+        )
+        .subcommand(
+            SubCommand::with_name("breach-check")
+                .about("Looks up a password, read without echo or from stdin, in a local HIBP SHA-1 list. Exits with 2 if found")
+                .arg(
+                    Arg::with_name("db")
+                        .long("db")
+                        .value_name("FILE")
+                        .required(true)
+                        .help("The list ordered by hash, e.g. pwned-passwords-sha1-ordered-by-hash.txt"),
+                )
+                .arg(
+                    Arg::with_name("build-index")
+                        .long("build-index")
+                        .help("Write FILE.idx with the offset of every 4 hex digit prefix for faster lookups, then exit"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("shred")
+                .about("Overwrites files with random data, then renames and removes them. Not reliable on copy-on-write filesystems or SSDs")
+                .arg(
+                    Arg::with_name("FILE")
+                        .required(true)
+                        .multiple(true)
+                        .help("The files to remove"),
+                )
+                .arg(
+                    Arg::with_name("passes")
+                        .long("passes")
+                        .value_name("N")
+                        .default_value("1")
+                        .help("How many times to overwrite the data"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("regex")
+                .about("Generates strings uniformly from a bounded regular expression")
+                .arg(
+                    Arg::with_name("PATTERN")
+                        .required(true)
+                        .help("The expression, e.g. '[A-Z]{4}-\\d{4}'. Unbounded quantifiers *, + and {n,} are not allowed"),
+                )
+                .arg(
+                    Arg::with_name("count")
+                        .short("c")
+                        .long("count")
+                        .value_name("N")
+                        .default_value("1")
+                        .help("How many strings to generate"),
+                )
+                .arg(
+                    Arg::with_name("check")
+                        .long("check")
+                        .help("Print the number of matching strings per length and the entropy without generating anything"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("key")
+                .about("Generates a raw symmetric key from the combined generator")
+                .arg(
+                    Arg::with_name("bytes")
+                        .long("bytes")
+                        .value_name("N")
+                        .default_value("32")
+                        .help("Key length in bytes"),
+                )
+                .arg(
+                    Arg::with_name("encoding")
+                        .short("e")
+                        .long("encoding")
+                        .value_name("ENCODING")
+                        .possible_values(&["hex", "base64", "raw"])
+                        .default_value("hex")
+                        .help("Output encoding of the key"),
+                )
+                .arg(
+                    Arg::with_name("out")
+                        .short("o")
+                        .long("out")
+                        .value_name("FILE")
+                        .help("Write the key to a new file with 0600 permissions instead of stdout. Existing files are not overwritten"),
+                ),
+        )
         .get_matches();
 
+    // Only the generators and the regex and bip39 subcommands write through --output, --out and --split-out
+    if let Some(name) = matches.subcommand_name().filter(|&name| name != "regex" && name != "bip39") {
+        if let Some(option) = ["output", "out", "split-out", "name-template", "qr", "qr-file", "qr-ec", "qr-payload", "wifi-ssid", "otp-label", "otp-issuer"].iter().find(|&&option| matches.is_present(option)) {
+            let hint = if name == "key" && option == &"out" { ", use keygen key --out FILE" } else { "" };
+            eprintln!("Error: --{} does not apply to keygen {}{}. Exiting.", option, name, hint);
+            std::process::exit(1);
+        }
+    }
+
+    if let Some(bench_matches) = matches.subcommand_matches("bench") {
+        let iterations = match bench_matches.value_of("iterations").unwrap().parse::<u32>() {
+            Ok(n) if n > 0 => n,
+            _ => {
+                eprintln!("Error: --iterations must be a positive number. Exiting.");
+                std::process::exit(1);
+            }
+        };
+        let bits = match bench_matches.value_of("bits").unwrap().parse::<u32>() {
+            Ok(n) if n > 0 => n,
+            _ => {
+                eprintln!("Error: --bits must be a positive number. Exiting.");
+                std::process::exit(1);
+            }
+        };
+        // clap has already checked the value against table and json
+        let output = bench::BenchOutput::from_str(bench_matches.value_of("output").unwrap()).unwrap();
+        bench::run_bench(iterations, bits, output);
+        std::process::exit(0);
+    }
+
+    if let Some(key_matches) = matches.subcommand_matches("key") {
+        run_key(key_matches);
+    }
+
+    if let Some(alphabets_matches) = matches.subcommand_matches("alphabets") {
+        if let Some(show_matches) = alphabets_matches.subcommand_matches("show") {
+            if let Err(e) = alphabet_info::print_alphabet(show_matches.value_of("NAME").unwrap()) {
+                eprintln!("Error: {}. Exiting.", e);
+                std::process::exit(1);
+            }
+        } else {
+            alphabet_info::print_alphabets();
+        }
+        std::process::exit(0);
+    }
+
+    if matches.subcommand_matches("check").is_some() {
+        run_check();
+    }
+
+    if let Some(breach_matches) = matches.subcommand_matches("breach-check") {
+        run_breach_check(breach_matches);
+    }
+
+    if let Some(shred_matches) = matches.subcommand_matches("shred") {
+        run_shred(shred_matches);
+    }
+
+    if let Some(mask_matches) = matches.subcommand_matches("mask") {
+        if let Some(check_matches) = mask_matches.subcommand_matches("check") {
+            let mask = check_matches.value_of("MASK").unwrap();
+            let positions = parse_mask_or_exit(mask, &custom_charsets(check_matches));
+            mask::print_audit(mask, &positions);
+            std::process::exit(0);
+        }
+        eprintln!("Error: Missing mask command. See keygen mask --help. Exiting.");
+        std::process::exit(1);
+    }
+
+    if let Some(wordlist_matches) = matches.subcommand_matches("wordlist") {
+        if let Some(check_matches) = wordlist_matches.subcommand_matches("check") {
+            let path = check_matches.value_of("FILE").unwrap();
+            let report = load_wordlist_or_exit(path);
+            wordlist::print_report(path, &report);
+            std::process::exit(if report.is_valid() { 0 } else { 1 });
+        }
+        eprintln!("Error: Missing wordlist command. See keygen wordlist --help. Exiting.");
+        std::process::exit(1);
+    }
+
+    let normal_form = normalize::NormalForm::from_str(matches.value_of("normalize").unwrap_or("nfc")).expect("Invalid normal form");
+
     let config = Config {
         debug: matches.is_present("debug"),
         bits: matches.value_of("bits").map(|b| b.parse().unwrap()).unwrap_or(256),
         alphabet: matches.value_of("alphabet").unwrap_or("commonsafe").to_string(),
+        charset: matches.value_of("charset").map(|c| c.to_string()),
+        layouts: matches.value_of("layouts").map(|l| l.to_string()),
+        wordlist: matches.value_of("wordlist").map(|w| w.to_string()),
+        dice: matches.is_present("dice"),
+        pad: matches.is_present("pad"),
+        require: matches.value_of("require").map(|r| r.to_string()),
+        rules: matches.value_of("rules").map(|r| r.to_string()),
+        mask: matches.value_of("mask").map(|m| m.to_string()),
+        custom_charsets: custom_charsets(&matches),
+        check_symbol: matches.is_present("check-symbol"),
+        include: matches.value_of("include").map(|c| c.to_string()),
+        fold_ascii: matches.is_present("fold-ascii"),
+        normalize: normal_form,
+        exclude: matches.value_of("exclude").map(|c| c.to_string()),
         count: matches.value_of("count").map(|i| i.parse::<usize>().unwrap_or(1)).unwrap_or(1),
-        delimiter: matches.value_of("delimiter").unwrap_or("").to_string(),
+        delimiter: match normalize::normalize(matches.value_of("delimiter").unwrap_or(""), normal_form) {
+            Some(delimiter) => delimiter,
+            None => {
+                eprintln!("Error: The delimiter has no ASCII form. Exiting.");
+                std::process::exit(1);
+            }
+        },
+        delimiter_set: matches.value_of("delimiter-set").map(|d| d.to_string()),
+        capitalize: matches.is_present("capitalize"),
+        unique: matches.is_present("unique"),
+        no_patterns: if matches.is_present("no-patterns") {
+            Some(matches.value_of("no-patterns").unwrap_or(patterns::DEFAULT_PATTERNS).to_string())
+        } else {
+            None
+        },
+        insert: matches.value_of("insert").map(|i| i.to_string()),
+        breach_db: matches.value_of("breach-db").map(|b| b.to_string()),
+        output: output::OutputFormat::from_str(matches.value_of("output").unwrap_or("text")).expect("Invalid output format"),
+        out: matches.value_of("out").map(|o| o.to_string()),
+        split_out: matches.value_of("split-out").map(|s| s.to_string()),
+        name_template: matches.value_of("name-template").unwrap_or("secret-{n}.txt").to_string(),
+        qr: matches.is_present("qr"),
+        qr_file: matches.value_of("qr-file").map(|q| q.to_string()),
+        qr_ec: matches.value_of("qr-ec").map(|q| q.to_string()),
+        qr_payload: matches.value_of("qr-payload").map(|q| q.to_string()),
+        wifi_ssid: matches.value_of("wifi-ssid").map(|w| w.to_string()),
+        otp_label: matches.value_of("otp-label").map(|o| o.to_string()),
+        otp_issuer: matches.value_of("otp-issuer").map(|o| o.to_string()),
+        syllables: matches.value_of("syllables").map(|s| s.parse::<usize>().unwrap_or(3)).unwrap_or(3).max(1),
+        threads: matches.value_of("threads").map(|t| t.parse::<usize>().unwrap_or(1)).unwrap_or(1).max(1),
 
         rngtest: if matches.is_present("rngtest") {
             let generator_str = matches.value_of("rngtest").unwrap();
@@ -219,6 +1376,21 @@ fn main() {
         }
     };
 
+    if let Some(regex_matches) = matches.subcommand_matches("regex") {
+        run_regex(regex_matches, config);
+    }
+
+    if let Some(bip39_matches) = matches.subcommand_matches("bip39") {
+        run_bip39(bip39_matches, config);
+    }
+
+    // The payload checks each secret, this refuses generator options that can not make base32 up front
+    if (config.qr || config.qr_file.is_some()) && config.qr_payload.as_deref() == Some("otpauth")
+        && (config.alphabet != "base32" || config.charset.is_some() || config.layouts.is_some() || config.wordlist.is_some() || config.rules.is_some() || config.mask.is_some() || !config.delimiter.is_empty()) {
+        eprintln!("Error: --qr-payload otpauth needs a base32 secret without delimiters, use -a base32. Exiting.");
+        std::process::exit(1);
+    }
+
     if let Some((generator, data_size, data_format)) = &config.rngtest {
         let num_values = *data_size as u64;
 
@@ -247,78 +1419,420 @@ fn main() {
     }
 
 
-    let mut alphabet_item: fn(usize) -> Option<String> = |_| None;
-    let mut alphabet_count: fn() -> usize = || 0;
-    
-    // Match the alphabet count and generator functions to the selected alphabet
-    match config.alphabet.as_str() {
-    
-        "words-fi" => {
-            alphabet_count = alphabet::alphabet_wordsfi_get_count;
-            alphabet_item = alphabet::alphabet_wordsfi_get_element;
+    let breach_db = config.breach_db.as_ref().map(|path| open_breach_db_or_exit(path));
+
+    // A mask fixes the charset of every position, generate and exit
+    if let Some(mask_string) = &config.mask {
+        let positions = parse_mask_or_exit(mask_string, &config.custom_charsets);
+        let entropy = mask::mask_entropy(&positions);
+        eprintln!("Mask entropy: {:.4} bits", entropy);
+
+        let output = output::Output {
+            format: config.output,
+            alphabet: "mask".to_string(),
+            alphabet_size: None,
+            entropy_bits: entropy,
+            elements: Some(positions.len()),
+            delimiter: String::new(),
+            source: source_json(&config),
+            qr: qr_output_or_exit(&config),
+            destination: destination_or_exit(&config),
+        };
+        generate_batch(config.count, config.threads, &output, |_| loop {
+            let mut password_string: String = positions.iter().map(|charset| charset[random::generate_index(charset.len())]).collect();
+            if !is_breached(&breach_db, &password_string) {
+                break password_string;
+            }
+            password_string.zeroize();
+        });
+
+        std::process::exit(0);
+    }
+
+    let password_rules = config.rules.as_ref().map(|descriptor| match passwordrules::parse_rules(descriptor) {
+        Ok(rules) => rules,
+        Err(e) => {
+            eprintln!("Error: Invalid --rules: {}. Exiting.", e);
+            std::process::exit(1);
         }
-        "commonsafe" => {
-            alphabet_count = alphabet::alphabet_commonsafe_get_count;
-            alphabet_item = alphabet::alphabet_commonsafe_get_element;
+    });
+
+    // Collect the elements of the selected alphabet, or of the custom charset
+    let mut dice_numbers: Option<Vec<String>> = None;
+    let mut elements: Vec<String> = if let Some(rules) = &password_rules {
+        rules.allowed.iter().map(|c| c.to_string()).collect()
+    } else if let Some(charset) = &config.charset {
+        parse_charset_or_exit(charset, "charset").iter().map(|c| c.to_string()).collect()
+    } else if let Some(spec) = &config.layouts {
+        match layout::parse_layouts(spec) {
+            Ok(layouts) => layout::common_characters(&layouts).iter().map(|c| c.to_string()).collect(),
+            Err(e) => {
+                eprintln!("Error: Invalid --layouts: {}. Exiting.", e);
+                std::process::exit(1);
+            }
         }
-        "normal" => {
-            alphabet_count = alphabet::alphabet_normal_get_count;
-            alphabet_item = alphabet::alphabet_normal_get_element;
+    } else if let Some(path) = &config.wordlist {
+        let report = load_wordlist_or_exit(path);
+        if !report.is_valid() {
+            for error in &report.errors {
+                eprintln!("Error: {}: {}", path, error);
+            }
+            eprintln!("Error: Invalid wordlist. Exiting.");
+            std::process::exit(1);
         }
-        "ascii" => {
-            alphabet_count = alphabet::alphabet_ascii_get_count;
-            alphabet_item = alphabet::alphabet_ascii_get_element;
+        if !report.empty_lines.is_empty() {
+            eprintln!("Warning: {}: skipped {} empty line(s)", path, report.empty_lines.len());
         }
-        "assembly" => {
-            alphabet_count = alphabet::alphabet_assembly_get_count;
-            alphabet_item = alphabet::alphabet_assembly_get_element;
+        if !report.duplicates.is_empty() {
+            eprintln!("Warning: {}: skipped {} duplicate word(s)", path, report.duplicates.len());
         }
-        _ => {
-            print!("Error: Unknown alphabet specified. Exiting");
+        if !report.is_prefix_free() && config.delimiter.is_empty() {
+            eprintln!("Warning: {}: {} word(s) are prefixes of other words. Without a delimiter some passphrases can be produced in more than one way.", path, report.prefixes.len());
+        }
+        eprintln!("Wordlist {}: {} words, {:.4} bits per word", path, report.words.len(), report.bits_per_word());
+        dice_numbers = report.dice_numbers;
+        report.words
+    } else {
+        dice_numbers = alphabet::alphabet_dice_numbers(&config.alphabet);
+        match alphabet::alphabet_get_elements(&config.alphabet) {
+            Some(elements) => elements,
+            None => {
+                eprintln!("Error: Unknown alphabet specified. Exiting.");
+                std::process::exit(1);
+            },
+        }
+    };
+
+    if let Some(include) = &config.include {
+        charset::include_chars(&mut elements, &parse_charset_or_exit(include, "include"));
+    }
+    if let Some(exclude) = &config.exclude {
+        charset::exclude_chars(&mut elements, &parse_charset_or_exit(exclude, "exclude"));
+    }
+    if config.fold_ascii {
+        let merged = charset::fold_elements(&mut elements);
+        if merged > 0 {
+            eprintln!("Warning: folding to ASCII merged {} duplicate element(s), {} left", merged, elements.len());
+        }
+    }
+    let (merged, dropped) = normalize::normalize_elements(&mut elements, config.normalize);
+    if dropped > 0 {
+        eprintln!("Warning: removed {} element(s) without an ASCII form", dropped);
+    }
+    if merged > 0 {
+        eprintln!("Warning: normalization merged {} duplicate element(s), {} left", merged, elements.len());
+    }
+
+    if elements.len() < 2 {
+        eprintln!("Error: The effective alphabet has {} element(s), at least 2 are needed. Exiting.", elements.len());
+        std::process::exit(1);
+    }
+
+    if config.debug {
+        if let Some(descriptor) = &config.rules {
+            eprintln!("Using rules: {}", descriptor);
+        } else if let Some(charset) = &config.charset {
+            eprintln!("Using charset: {}", charset);
+        } else if let Some(layouts) = &config.layouts {
+            eprintln!("Using layouts: {}", layouts);
+        } else if let Some(path) = &config.wordlist {
+            eprintln!("Using wordlist: {}", path);
+        } else {
+            eprintln!("Using alphabet: {}", config.alphabet);
+        }
+        if elements.iter().all(|element| normalize::grapheme_count(element) == 1) {
+            eprintln!("Effective alphabet: {}", elements.concat());
+        }
+        eprintln!("alphabet_count: {}", elements.len());
+        eprintln!("request bits: {}", config.bits);
+    }
+
+    // Dice rolls for each element, either from a diceware file or in list order
+    let dice_rolls: Option<Vec<String>> = if config.dice {
+        let dice = match wordlist::dice_per_word(elements.len()) {
+            Some(dice) => dice,
+            None => {
+                eprintln!("Error: --dice needs a list of 6^n elements, this one has {}. Exiting.", elements.len());
+                std::process::exit(1);
+            }
+        };
+        let rolls = dice_numbers.unwrap_or_else(|| (0..elements.len()).map(|i| wordlist::dice_rolls_for_index(i, dice)).collect());
+        if rolls.iter().any(|roll| roll.len() != dice as usize) {
+            eprintln!("Error: The dice numbers of the wordlist do not cover all {} rolls. Exiting.", elements.len());
             std::process::exit(1);
+        }
+        Some(rolls)
+    } else {
+        None
+    };
+
+    let requirements = match &config.require {
+        Some(spec) => match policy::parse_requirements(spec).and_then(|r| policy::check_alphabet(&elements, &r).map(|_| r)) {
+            Ok(requirements) => requirements,
+            Err(e) => {
+                eprintln!("Error: Invalid --require: {}. Exiting.", e);
+                std::process::exit(1);
+            }
         },
+        None => Vec::new(),
+    };
+
+    let decorations = decorate::Decorations {
+        capitalize: config.capitalize,
+        insert: config.insert.as_deref().map(decorate::insert_chars).unwrap_or_default(),
+        // Taken literally, a dash between two delimiters is not a range
+        delimiters: config.delimiter_set.as_ref().map(|set| {
+            let mut delimiters: Vec<char> = set.chars().collect();
+            delimiters.sort();
+            delimiters.dedup();
+            delimiters
+        }).unwrap_or_default(),
+    };
+    if let Err(e) = decorations.check(&elements) {
+        eprintln!("Error: {}. Exiting.", e);
+        std::process::exit(1);
     }
 
-    if config.debug {
-        println!("Using alphabet: {}", config.alphabet);
-        println!("alphabet_count: {}", alphabet_count());
-        println!("request bits: {}", config.bits);
+    let pattern_filter = config.no_patterns.as_ref().map(|spec| match patterns::parse_patterns(spec) {
+        Ok(filter) => filter,
+        Err(e) => {
+            eprintln!("Error: Invalid --no-patterns: {}. Exiting.", e);
+            std::process::exit(1);
+        }
+    });
+
+    // Encodings with a power of two size are produced from whole bytes instead of per-symbol sampling
+    let exact_encoding = if decorations.is_empty() && !config.unique && config.no_patterns.is_none() && config.charset.is_none() && config.layouts.is_none() && config.wordlist.is_none() && config.include.is_none() && config.exclude.is_none() && requirements.is_empty() && password_rules.is_none() {
+        encoding::exact_bits_per_symbol(&config.alphabet).map(|_| config.alphabet.as_str())
+    } else {
+        None
+    };
+
+    if config.pad && !exact_encoding.is_some_and(encoding::supports_padding) {
+        eprintln!("Error: --pad is only supported with the base32 and base64url alphabets. Exiting.");
+        std::process::exit(1);
+    }
+    if config.check_symbol && exact_encoding != Some("crockford32") {
+        eprintln!("Error: --check-symbol is only supported with the crockford32 alphabet. Exiting.");
+        std::process::exit(1);
+    }
+
+    if let Some(encoding_name) = exact_encoding {
+        let num_bytes = config.bits.div_ceil(8) as usize;
+
+        if config.debug {
+            eprintln!("Exact-bit output: {} bytes, {} bits", num_bytes, num_bytes * 8);
+        }
+
+        let bits_per_symbol = encoding::exact_bits_per_symbol(encoding_name).unwrap();
+        let output = output::Output {
+            format: config.output,
+            alphabet: encoding_name.to_string(),
+            alphabet_size: Some(1 << bits_per_symbol),
+            entropy_bits: (num_bytes * 8) as f64,
+            elements: Some((num_bytes * 8).div_ceil(bits_per_symbol as usize)),
+            delimiter: config.delimiter.clone(),
+            source: source_json(&config),
+            qr: qr_output_or_exit(&config),
+            destination: destination_or_exit(&config),
+        };
+        generate_batch(config.count, config.threads, &output, |_| loop {
+            let mut bytes = random::generate_bytes(num_bytes);
+            let mut encoded = encoding::encode(&bytes, encoding_name, config.pad);
+            bytes.zeroize();
+            if config.check_symbol {
+                let check = encoding::crockford_check_symbol(&encoded);
+                encoded.push(check);
+            }
+            let mut password_string = if config.delimiter.is_empty() {
+                encoded
+            } else {
+                let symbols: Vec<String> = encoded.chars().map(|c| c.to_string()).collect();
+                encoded.zeroize();
+                symbols.join(&config.delimiter)
+            };
+            if !is_breached(&breach_db, &password_string) {
+                break password_string;
+            }
+            password_string.zeroize();
+        });
+
+        std::process::exit(0);
+    }
+
+    // Pronounceable syllables are grouped into pseudo-words, other alphabets have the delimiter between every element
+    let group_size = if config.alphabet.starts_with("pronounceable-") && config.charset.is_none() && config.layouts.is_none() && config.wordlist.is_none() && password_rules.is_none() {
+        config.syllables
+    } else {
+        1
+    };
+    if group_size > 1 && !decorations.is_empty() {
+        eprintln!("Error: Decorations are not supported with the pronounceable alphabets. Exiting.");
+        std::process::exit(1);
     }
 
     // Find the number of characters needed
-    let bits_per_element= (alphabet_count() as f64).log2();
-    let num_elements = (config.bits as f64 / bits_per_element as f64).ceil() as u32;
+    let bits_per_element= (elements.len() as f64).log2();
+    let mut num_elements = elements_for_bits(config.bits, elements.len());
+    let mut entropy_bits = num_elements as f64 * bits_per_element;
+
+    // With requirements only the valid passwords count, add elements until they are enough
+    if !requirements.is_empty() {
+        let min_total: usize = requirements.iter().map(|r| r.min).sum();
+        num_elements = num_elements.max(min_total as u32);
+        loop {
+            entropy_bits = policy::log2_big(&policy::count_valid(&elements, num_elements as usize, &requirements));
+            if entropy_bits >= config.bits as f64 {
+                break;
+            }
+            num_elements += 1;
+        }
+
+        let accepted_fraction = (entropy_bits - num_elements as f64 * bits_per_element).exp2();
+        if accepted_fraction < 1e-6 {
+            eprintln!("Error: The requirements accept only {:e} of the passwords. Exiting.", accepted_fraction);
+            std::process::exit(1);
+        }
+        if config.debug {
+            eprintln!("Accepted fraction: {}", accepted_fraction);
+        }
+    }
+
+    // With passwordrules the length has to fit between minlength and maxlength
+    if let Some(rules) = &password_rules {
+        num_elements = num_elements.max(rules.min_length.unwrap_or(1) as u32);
+        loop {
+            if rules.max_length.is_some_and(|max| num_elements as usize > max) {
+                eprintln!("Error: The rules can not reach {} bits within maxlength {}. Exiting.", config.bits, rules.max_length.unwrap());
+                std::process::exit(1);
+            }
+            entropy_bits = policy::log2_big(&rules.count_valid(num_elements as usize));
+            if entropy_bits >= config.bits as f64 {
+                break;
+            }
+            num_elements += 1;
+        }
+
+        let accepted_fraction = (entropy_bits - num_elements as f64 * bits_per_element).exp2();
+        if accepted_fraction < 1e-6 {
+            eprintln!("Error: The rules accept only {:e} of the passwords. Exiting.", accepted_fraction);
+            std::process::exit(1);
+        }
+        eprintln!("Rules: {} characters allowed, length {}, {:.2} bits", elements.len(), num_elements, entropy_bits);
+    }
+
+    // Without replacement each element has one choice less than the previous: log2 of the falling factorial n (n-1) .. (n-k+1)
+    let element_bits = |k: u32| -> f64 {
+        if config.unique {
+            (0..k as usize).map(|i| ((elements.len() - i) as f64).log2()).sum()
+        } else {
+            k as f64 * bits_per_element
+        }
+    };
+
+    // Decorations add bits of their own, fewer elements may be enough
+    if config.unique || !decorations.is_empty() {
+        num_elements = 1;
+        while element_bits(num_elements) + decorations.bits(num_elements as usize) < config.bits as f64 {
+            if config.unique && num_elements as usize >= elements.len() {
+                eprintln!("Error: --unique can not reach {} bits with {} distinct elements. Exiting.", config.bits, elements.len());
+                std::process::exit(1);
+            }
+            num_elements += 1;
+        }
+        entropy_bits = element_bits(num_elements) + decorations.bits(num_elements as usize);
+        if !decorations.is_empty() {
+            eprintln!("Decorations: {} elements, {:.2} bits of which {:.2} from decorations", num_elements, entropy_bits, decorations.bits(num_elements as usize));
+        }
+    }
+
+    // Passwords with weak patterns are rejected, add elements until the accepted ones alone reach the bits
+    if let Some(filter) = &pattern_filter {
+        loop {
+            let mut rng = random::EstimationRng::new();
+            let accepted = patterns::estimate_accepted(filter, PATTERN_SAMPLES, || {
+                let indices: Vec<usize> = (0..num_elements).map(|_| rng.index(elements.len())).collect();
+                join_grouped(&elements, &indices, &config.delimiter, group_size)
+            });
+            if accepted < 1e-3 {
+                eprintln!("Error: The pattern filter rejects nearly all passwords of {} elements. Exiting.", num_elements);
+                std::process::exit(1);
+            }
+            entropy_bits = element_bits(num_elements) + accepted.log2();
+            if entropy_bits >= config.bits as f64 {
+                eprintln!("Patterns: about {:.2}% rejected, {} elements, {:.2} bits", (1.0 - accepted) * 100.0, num_elements, entropy_bits);
+                break;
+            }
+            num_elements += 1;
+        }
+    }
 
     if config.debug {
-        println!("Bits per element: {}", bits_per_element);
-        println!("Num of elements: {}", num_elements);
+        eprintln!("Bits per element: {}", bits_per_element);
+        eprintln!("Num of elements: {}", num_elements);
+        eprintln!("Entropy bits: {}", entropy_bits);
+        let average_graphemes = elements.iter().map(|element| normalize::grapheme_count(element)).sum::<usize>() as f64 / elements.len() as f64;
+        eprintln!("Average length: {:.1} characters", average_graphemes * num_elements as f64 + normalize::grapheme_count(&config.delimiter) as f64 * num_elements.saturating_sub(1) as f64);
     }
 
+    let output = output::Output {
+        format: config.output,
+        alphabet: if password_rules.is_some() {
+            "rules".to_string()
+        } else if config.charset.is_some() {
+            "charset".to_string()
+        } else if config.layouts.is_some() {
+            "layouts".to_string()
+        } else if config.wordlist.is_some() {
+            "wordlist".to_string()
+        } else {
+            config.alphabet.clone()
+        },
+        alphabet_size: Some(elements.len()),
+        entropy_bits,
+        elements: Some(num_elements as usize),
+        delimiter: config.delimiter.clone(),
+        source: source_json(&config),
+        qr: qr_output_or_exit(&config),
+        destination: destination_or_exit(&config),
+    };
+
     // Create the password(s)
-    for _ in 0..config.count {
-        let mut password_string = String::new();
-
-        for i in 0..num_elements {
-            // pull out a random value that does not result in modulo bias
-            let mut random_value: Option<u64> = None;
-            while random_value.is_none() {
-                let val = generate_u64();
-                if val.unwrap() <= (u64::MAX - (alphabet_count() as u64)) {
-                    random_value = val;
+    generate_batch(config.count, config.threads, &output, |_| {
+        // Whole-password rejection keeps the output uniform over the passwords meeting the requirements
+        let pick = || if config.unique { generate_unique_indices(elements.len(), num_elements) } else { generate_indices(elements.len(), num_elements) };
+        let (indices, mut password_string) = loop {
+            let mut indices = pick();
+            while !policy::satisfies(&elements, &indices, &requirements) || !satisfies_rules(&password_rules, &elements, &indices) {
+                indices = pick();
+            }
+            let mut password_string = if decorations.is_empty() {
+                join_grouped(&elements, &indices, &config.delimiter, group_size)
+            } else {
+                decorations.apply(&elements, &indices, &config.delimiter)
+            };
+            if let Some(filter) = &pattern_filter {
+                while !filter.accepts(&password_string) {
+                    password_string.zeroize();
+                    indices = pick();
+                    password_string = join_grouped(&elements, &indices, &config.delimiter, group_size);
                 }
             }
-            
-            // get the corresponding alphabet element
-            let random_index = (random_value.unwrap() % alphabet_count() as u64) as usize;
-            let random_element = alphabet_item(random_index).unwrap();
-            password_string.push_str(&random_element);
-            if i < num_elements - 1 {
-                password_string.push_str(&config.delimiter);
+            if !is_breached(&breach_db, &password_string) {
+                break (indices, password_string);
+            }
+            password_string.zeroize();
+        };
+        match &dice_rolls {
+            Some(rolls) => {
+                let rolls: Vec<&str> = indices.iter().map(|&index| rolls[index].as_str()).collect();
+                let line = format!("{}\t{}", password_string, rolls.join(" "));
+                password_string.zeroize();
+                line
             }
+            None => password_string,
         }
-
-        println!("{}", password_string);
-    }
+    });
 
     std::process::exit(0);
 }
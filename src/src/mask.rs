@@ -0,0 +1,106 @@
+/* mask.rs
+
+   Hashcat style mask templates, e.g. "?u?l?l?l-?d?d?d?d?s".
+   Built-in charsets: ?l lower, ?u upper, ?d digits, ?s symbols (with space), ?a all of these,
+   ?h and ?H lower and upper case hex. ?1 .. ?4 refer to custom charsets given with -1 .. -4,
+   which may themselves use the built-in charsets. "??" is a literal '?', anything else is a literal.
+   The entropy of a mask is the sum of log2 of the charset size at each position.
+*/
+
+const CHARSET_LOWER: &str = "abcdefghijklmnopqrstuvwxyz";
+const CHARSET_UPPER: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+const CHARSET_DIGIT: &str = "0123456789";
+const CHARSET_SYMBOL: &str = " !\"#$%&'()*+,-./:;<=>?@[\\]^_`{|}~";
+
+fn builtin_charset(c: char) -> Option<String> {
+    match c {
+        'l' => Some(CHARSET_LOWER.to_string()),
+        'u' => Some(CHARSET_UPPER.to_string()),
+        'd' => Some(CHARSET_DIGIT.to_string()),
+        's' => Some(CHARSET_SYMBOL.to_string()),
+        'a' => Some(format!("{}{}{}{}", CHARSET_LOWER, CHARSET_UPPER, CHARSET_DIGIT, CHARSET_SYMBOL)),
+        'h' => Some(format!("{}abcdef", CHARSET_DIGIT)),
+        'H' => Some(format!("{}ABCDEF", CHARSET_DIGIT)),
+        _ => None,
+    }
+}
+
+/* Expands a custom charset definition. Built-in placeholders are allowed, custom ones are not. */
+fn expand_custom(definition: &str) -> Result<Vec<char>, String> {
+    let mut result: Vec<char> = Vec::new();
+    let mut chars = definition.chars();
+
+    while let Some(c) = chars.next() {
+        let expanded: Vec<char> = if c == '?' {
+            match chars.next() {
+                Some('?') => vec!['?'],
+                Some(p) => builtin_charset(p).ok_or_else(|| format!("unknown charset ?{} in custom charset", p))?.chars().collect(),
+                None => return Err("custom charset ends with '?'".to_string()),
+            }
+        } else {
+            vec![c]
+        };
+        for e in expanded {
+            if !result.contains(&e) {
+                result.push(e);
+            }
+        }
+    }
+
+    if result.is_empty() {
+        return Err("empty custom charset".to_string());
+    }
+    Ok(result)
+}
+
+
+/* Parses the mask into the set of possible characters at each position */
+pub fn parse_mask(mask: &str, custom: &[Option<String>; 4]) -> Result<Vec<Vec<char>>, String> {
+    let mut positions: Vec<Vec<char>> = Vec::new();
+    let mut chars = mask.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '?' {
+            positions.push(vec![c]);
+            continue;
+        }
+
+        let placeholder = chars.next().ok_or_else(|| "mask ends with '?'".to_string())?;
+        let charset: Vec<char> = match placeholder {
+            '?' => vec!['?'],
+            '1'..='4' => {
+                let n = placeholder.to_digit(10).unwrap() as usize;
+                match &custom[n - 1] {
+                    Some(definition) => expand_custom(definition)?,
+                    None => return Err(format!("?{} used but -{} is not given", n, n)),
+                }
+            }
+            p => builtin_charset(p).ok_or_else(|| format!("unknown charset ?{}", p))?.chars().collect(),
+        };
+        positions.push(charset);
+    }
+
+    if positions.is_empty() {
+        return Err("empty mask".to_string());
+    }
+    Ok(positions)
+}
+
+/* Exact entropy of the mask in bits */
+pub fn mask_entropy(positions: &[Vec<char>]) -> f64 {
+    positions.iter().map(|charset| (charset.len() as f64).log2()).sum()
+}
+
+/* Prints the per-position breakdown. Used by "keygen mask check". */
+pub fn print_audit(mask: &str, positions: &[Vec<char>]) {
+    println!("Mask: {}", mask);
+    println!("Length: {}", positions.len());
+    for (i, charset) in positions.iter().enumerate() {
+        if charset.len() == 1 {
+            println!("  {:>3}: literal '{}'", i + 1, charset[0]);
+        } else {
+            println!("  {:>3}: {} characters, {:.4} bits", i + 1, charset.len(), (charset.len() as f64).log2());
+        }
+    }
+    println!("Entropy: {:.4} bits", mask_entropy(positions));
+}
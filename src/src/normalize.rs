@@ -0,0 +1,79 @@
+/* normalize.rs
+
+   Unicode normalization of the generated output and grapheme based lengths.
+   The same passphrase must type the same on every system, so the elements are normalized
+   before generation, never the finished output: forms that merge elements (ascii) then
+   shrink the alphabet and the entropy is computed from what is left.
+   Lengths count grapheme clusters, so "ä" is one character whether precomposed or not.
+*/
+
+use std::str::FromStr;
+
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::charset;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum NormalForm {
+    Nfc,
+    Nfd,
+    Ascii,
+}
+
+impl FromStr for NormalForm {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "nfc" => Ok(NormalForm::Nfc),
+            "nfd" => Ok(NormalForm::Nfd),
+            "ascii" => Ok(NormalForm::Ascii),
+            _ => Err(()),
+        }
+    }
+}
+
+impl NormalForm {
+    pub fn name(&self) -> &'static str {
+        match self {
+            NormalForm::Nfc => "nfc",
+            NormalForm::Nfd => "nfd",
+            NormalForm::Ascii => "ascii",
+        }
+    }
+}
+
+/* Normalizes the string. For ascii, letters are decomposed and their marks dropped;
+   None if something is left that has no ASCII form.
+*/
+pub fn normalize(s: &str, form: NormalForm) -> Option<String> {
+    match form {
+        NormalForm::Nfc => Some(s.nfc().collect()),
+        NormalForm::Nfd => Some(s.nfd().collect()),
+        NormalForm::Ascii => {
+            let folded: String = charset::ascii_fold(s).nfd().filter(|c| !is_combining_mark(*c)).collect();
+            if folded.is_ascii() { Some(folded) } else { None }
+        }
+    }
+}
+
+/* Number of user-perceived characters */
+pub fn grapheme_count(s: &str) -> usize {
+    s.graphemes(true).count()
+}
+
+/* Normalizes every element, then removes the duplicates this creates.
+   Returns (merged, dropped): elements merged with another one, and elements without an ASCII form.
+*/
+pub fn normalize_elements(elements: &mut Vec<String>, form: NormalForm) -> (usize, usize) {
+    let before = elements.len();
+    let mut normalized: Vec<String> = elements.iter().filter_map(|element| normalize(element, form)).collect();
+    let dropped = before - normalized.len();
+
+    charset::dedup_elements(&mut normalized);
+    let merged = before - dropped - normalized.len();
+
+    *elements = normalized;
+    (merged, dropped)
+}
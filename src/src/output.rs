@@ -0,0 +1,259 @@
+/* output.rs
+
+   Output of the generated secrets: plain text, one secret per line, or JSON with one object
+   per line (NDJSON), so a batch can be read line by line and a single secret is a JSON document.
+
+   Each object carries the secret, its length in characters, the exact entropy and the settings
+   it was made with. Diagnostics never go to stdout, so it holds nothing but the secrets.
+
+   The secrets go to stdout, to one new file (--out) or to one new file per secret (--split-out),
+   the files created through secure_file. Each secret can also be shown as a QR code on stdout
+   and written to an SVG or PNG file.
+*/
+
+use std::fs::File;
+use std::io::Write;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use zeroize::Zeroize;
+
+use crate::normalize;
+use crate::qr;
+use crate::random;
+use crate::secure_file;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+impl FromStr for OutputFormat {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            _ => Err(()),
+        }
+    }
+}
+
+pub enum Destination {
+    Stdout,
+    File { file: File, path: String },
+    /* {n} in the template is replaced by the 1-based number of the secret, padded to width digits */
+    Split { dir: secure_file::SecretDir, template: String, width: usize },
+}
+
+impl Destination {
+    pub fn file(path: &str) -> Result<Self, String> {
+        let file = secure_file::create_secret_file(path)?;
+        Ok(Destination::File { file, path: path.to_string() })
+    }
+
+    pub fn split(dir: &str, template: &str, count: usize) -> Result<Self, String> {
+        if template.contains('/') || template.contains('\\') {
+            return Err(format!("the name template \"{}\" may not contain a path separator", template));
+        }
+        if count > 1 && !template.contains("{n}") {
+            return Err(format!("the name template \"{}\" needs {{n}} for more than one secret", template));
+        }
+        let dir = secure_file::prepare_secret_dir(dir)?;
+        Ok(Destination::Split { dir, template: template.to_string(), width: count.to_string().len() })
+    }
+}
+
+/* {n} in a file name becomes the 1-based number of the secret, padded to width digits */
+fn numbered(template: &str, index: usize, width: usize) -> String {
+    template.replace("{n}", &format!("{:0width$}", index + 1, width = width))
+}
+
+pub struct QrOutput {
+    pub ec: qr::ErrorCorrection,
+    pub payload: qr::Payload,
+    pub terminal: bool,
+    file: Option<String>,
+    width: usize,
+}
+
+impl QrOutput {
+    /* With split, the QR code files go into the --split-out directory and file is a name there */
+    pub fn new(ec: qr::ErrorCorrection, payload: qr::Payload, terminal: bool, file: Option<&str>, count: usize, split: bool) -> Result<Self, String> {
+        if let Some(path) = file {
+            if !path.ends_with(".svg") && !path.ends_with(".png") {
+                return Err(format!("the QR code file \"{}\" must end with .svg or .png", path));
+            }
+            if split && (path.contains('/') || path.contains('\\')) {
+                return Err(format!("the QR code file \"{}\" is a name in the --split-out directory and may not contain a path separator", path));
+            }
+            if count > 1 && !path.contains("{n}") {
+                return Err(format!("the QR code file \"{}\" needs {{n}} for more than one secret", path));
+            }
+        }
+        Ok(QrOutput { ec, payload, terminal, file: file.map(|f| f.to_string()), width: count.to_string().len() })
+    }
+
+    fn encode(&self, secret: &str) -> Result<qr::QrCode, String> {
+        let mut payload = self.payload.build(secret)?;
+        let code = qr::QrCode::encode_bytes(payload.as_bytes(), self.ec);
+        payload.zeroize();
+        code
+    }
+
+    /* Shows the QR code on stdout and writes the QR code file, into the directory of a split destination */
+    fn write(&self, index: usize, code: &qr::QrCode, destination: &Destination) -> Result<(), String> {
+        if self.terminal {
+            print!("{}", code.to_terminal());
+        }
+        if let Some(template) = &self.file {
+            let path = numbered(template, index, self.width);
+            let mut data = if path.ends_with(".svg") { code.to_svg().into_bytes() } else { code.to_png() };
+            let result = match destination {
+                Destination::Split { dir, .. } => dir.write(&path, &data),
+                _ => secure_file::write_secret_file(&path, &data),
+            };
+            data.zeroize();
+            result?;
+        }
+        Ok(())
+    }
+}
+
+/* What is common to every secret of a batch */
+pub struct Output {
+    pub format: OutputFormat,
+    pub alphabet: String,
+    /* None when the positions have different alphabets, as with a mask */
+    pub alphabet_size: Option<usize>,
+    pub entropy_bits: f64,
+    /* None when the number of symbols drawn varies, as with a regular expression */
+    pub elements: Option<usize>,
+    pub delimiter: String,
+    /* Rendered JSON object of the options the secrets were made with */
+    pub source: String,
+    pub destination: Destination,
+    pub qr: Option<QrOutput>,
+}
+
+/* A JSON string literal */
+pub fn json_string(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/* A JSON object from names and already rendered values */
+pub fn json_object(fields: &[(&str, String)]) -> String {
+    let fields: Vec<String> = fields.iter().map(|(name, value)| format!("{}:{}", json_string(name), value)).collect();
+    format!("{{{}}}", fields.join(","))
+}
+
+/* The current time in UTC as RFC 3339, e.g. 2024-05-01T12:00:00Z */
+fn timestamp() -> String {
+    let seconds = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let (days, time) = (seconds / 86400, seconds % 86400);
+
+    // Days since the epoch to a civil date, Howard Hinnant's days_from_civil inverted
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z", year, month, day, time / 3600, time % 3600 / 60, time % 60)
+}
+
+impl Output {
+    /* One secret in the selected format, with the line feed */
+    fn line(&self, secret: &str) -> String {
+        match self.format {
+            OutputFormat::Text => format!("{}\n", secret),
+            OutputFormat::Json => {
+                let metadata = json_object(&[
+                    ("alphabet", json_string(&self.alphabet)),
+                    ("alphabet_size", self.alphabet_size.map_or("null".to_string(), |size| size.to_string())),
+                    ("length", normalize::grapheme_count(secret).to_string()),
+                    ("entropy_bits", format!("{:.4}", self.entropy_bits)),
+                    ("elements", self.elements.map_or("null".to_string(), |elements| elements.to_string())),
+                    ("delimiter", json_string(&self.delimiter)),
+                    ("source", self.source.clone()),
+                    ("drbg", json_string(random::DRBG_NAME)),
+                    ("version", json_string(env!("CARGO_PKG_VERSION"))),
+                    ("timestamp", json_string(&timestamp())),
+                ]);
+                // The secret is written straight into the line so that every copy of it can be zeroized
+                let mut secret_json = json_string(secret);
+                let mut line = String::with_capacity(secret_json.len() + metadata.len() + 16);
+                line.push_str("{\"secret\":");
+                line.push_str(&secret_json);
+                line.push(',');
+                line.push_str(&metadata[1..]);
+                line.push('\n');
+                secret_json.zeroize();
+                line
+            }
+        }
+    }
+
+    /* Writes secret number index of the batch, counting from 0 */
+    pub fn write(&self, index: usize, secret: &str) -> Result<(), String> {
+        // Encode first, so that a secret the QR code can not hold is not written either
+        let code = match &self.qr {
+            Some(qr) => Some(qr.encode(secret)?),
+            None => None,
+        };
+
+        let mut line = self.line(secret);
+        let result = match &self.destination {
+            Destination::Stdout => {
+                print!("{}", line);
+                Ok(())
+            }
+            Destination::File { file, path } => {
+                let mut file: &File = file;
+                // A partly written batch is removed, like a partly written single secret file
+                file.write_all(line.as_bytes()).map_err(|e| {
+                    let _ = std::fs::remove_file(path);
+                    format!("Failed to write {}: {}", path, e)
+                })
+            }
+            Destination::Split { dir, template, width } => dir.write(&numbered(template, index, *width), line.as_bytes()),
+        };
+        line.zeroize();
+        result?;
+
+        match (&self.qr, &code) {
+            (Some(qr), Some(code)) => qr.write(index, code, &self.destination),
+            _ => Ok(()),
+        }
+    }
+
+    /* Syncs the --out file to disk once the batch is written */
+    pub fn finish(&self) -> Result<(), String> {
+        match &self.destination {
+            Destination::File { file, path } => file.sync_all().map_err(|e| {
+                let _ = std::fs::remove_file(path);
+                format!("Failed to sync {}: {}", path, e)
+            }),
+            _ => Ok(()),
+        }
+    }
+}
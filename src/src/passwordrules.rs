@@ -0,0 +1,266 @@
+/* passwordrules.rs
+
+   Parses the passwordrules descriptor syntax published by sites, e.g.
+   "minlength: 20; maxlength: 32; required: lower; required: upper; allowed: [-().&@?'#,/\"+]; max-consecutive: 2"
+   https://developer.apple.com/password-rules/
+
+   Character classes are built from the printable ASCII alphabet. Each "required" rule needs at least
+   one character from the union of its classes, "allowed" widens the set without a requirement.
+   Unknown rule names are ignored, as the spec says.
+*/
+
+use num_bigint::BigUint;
+use num_traits::{One, Zero};
+
+use crate::alphabet;
+
+/* Limit on the distinct required classes, count_valid() goes through every subset of them */
+const MAX_REQUIRED: usize = 16;
+
+pub struct PasswordRules {
+    pub allowed: Vec<char>,
+    pub required: Vec<Vec<char>>,
+    pub max_consecutive: Option<usize>,
+    pub min_length: Option<usize>,
+    pub max_length: Option<usize>,
+}
+
+
+fn ascii_printable() -> Vec<char> {
+    (0..alphabet::alphabet_ascii_get_count())
+        .map(|n| alphabet::alphabet_ascii_get_element(n).unwrap().chars().next().unwrap())
+        .collect()
+}
+
+/* Named classes of the passwordrules syntax */
+fn named_class(name: &str) -> Result<Vec<char>, String> {
+    let printable = ascii_printable();
+    match name {
+        "upper" => Ok(printable.into_iter().filter(|c| c.is_ascii_uppercase()).collect()),
+        "lower" => Ok(printable.into_iter().filter(|c| c.is_ascii_lowercase()).collect()),
+        "digit" => Ok(printable.into_iter().filter(|c| c.is_ascii_digit()).collect()),
+        "special" => Ok(printable.into_iter().filter(|c| !c.is_ascii_alphanumeric()).collect()),
+        // Unicode can not be enumerated, printable ASCII is the usable subset
+        "ascii-printable" | "unicode" => Ok(printable),
+        _ => Err(format!("unknown character class \"{}\"", name)),
+    }
+}
+
+fn add_unique(set: &mut Vec<char>, chars: &[char]) {
+    for &c in chars {
+        if !set.contains(&c) {
+            set.push(c);
+        }
+    }
+}
+
+
+/* Splits the descriptor into (name, value) pairs at ';', ignoring ';' inside custom [...] classes */
+fn split_rules(descriptor: &str) -> Vec<(String, String)> {
+    let mut rules = Vec::new();
+    let mut current = String::new();
+    let mut in_class = false;
+    let chars: Vec<char> = descriptor.chars().collect();
+
+    for (i, &c) in chars.iter().enumerate() {
+        match c {
+            '[' if !in_class => in_class = true,
+            // "]" as the last member of a class is written "]]"
+            ']' if in_class && chars.get(i + 1) != Some(&']') => in_class = false,
+            ';' if !in_class => {
+                rules.push(std::mem::take(&mut current));
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+    rules.push(current);
+
+    rules.iter()
+        .filter_map(|rule| rule.split_once(':'))
+        .map(|(name, value)| (name.trim().to_lowercase(), value.trim().to_string()))
+        .collect()
+}
+
+/* Parses a class list like "upper, digit, [-_]" into the union of the classes */
+fn parse_classes(value: &str) -> Result<Vec<char>, String> {
+    let mut result: Vec<char> = Vec::new();
+    let chars: Vec<char> = value.chars().collect();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        if c == ',' || c.is_whitespace() {
+            i += 1;
+        } else if c == '[' {
+            let mut end = i + 1;
+            while end < chars.len() && !(chars[end] == ']' && chars.get(end + 1) != Some(&']')) {
+                end += 1;
+            }
+            if end >= chars.len() {
+                return Err("unterminated custom character class".to_string());
+            }
+            let custom: Vec<char> = chars[i + 1..end].iter().copied().filter(|c| (' '..='~').contains(c)).collect();
+            add_unique(&mut result, &custom);
+            i = end + 1;
+        } else {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '-') {
+                i += 1;
+            }
+            if i == start {
+                return Err(format!("unexpected character '{}'", c));
+            }
+            let name: String = chars[start..i].iter().collect::<String>().to_lowercase();
+            add_unique(&mut result, &named_class(&name)?);
+        }
+    }
+
+    Ok(result)
+}
+
+fn parse_number(name: &str, value: &str) -> Result<usize, String> {
+    match value.parse::<usize>() {
+        Ok(n) if n > 0 => Ok(n),
+        _ => Err(format!("invalid value \"{}\" for {}", value, name)),
+    }
+}
+
+
+pub fn parse_rules(descriptor: &str) -> Result<PasswordRules, String> {
+    let mut rules = PasswordRules {
+        allowed: Vec::new(),
+        required: Vec::new(),
+        max_consecutive: None,
+        min_length: None,
+        max_length: None,
+    };
+
+    for (name, value) in split_rules(descriptor) {
+        match name.as_str() {
+            "required" => {
+                let class = parse_classes(&value)?;
+                add_unique(&mut rules.allowed, &class);
+                if !class.is_empty() {
+                    rules.required.push(class);
+                }
+            }
+            "allowed" => add_unique(&mut rules.allowed, &parse_classes(&value)?),
+            "max-consecutive" => {
+                let n = parse_number(&name, &value)?;
+                rules.max_consecutive = Some(rules.max_consecutive.map_or(n, |m| m.min(n)));
+            }
+            "minlength" => {
+                let n = parse_number(&name, &value)?;
+                rules.min_length = Some(rules.min_length.map_or(n, |m| m.max(n)));
+            }
+            "maxlength" => {
+                let n = parse_number(&name, &value)?;
+                rules.max_length = Some(rules.max_length.map_or(n, |m| m.min(n)));
+            }
+            _ => {}
+        }
+    }
+
+    if rules.allowed.is_empty() {
+        rules.allowed = ascii_printable();
+    }
+
+    // A class containing another required class is met whenever that one is, so only the smallest are kept
+    let required = std::mem::take(&mut rules.required);
+    for (i, class) in required.iter().enumerate() {
+        let implied = required.iter().enumerate().any(|(j, other)| {
+            j != i && other.iter().all(|c| class.contains(c)) && (other.len() < class.len() || j < i)
+        });
+        if !implied {
+            rules.required.push(class.clone());
+        }
+    }
+    if rules.required.len() > MAX_REQUIRED {
+        return Err(format!("the rules have {} distinct required classes, at most {} are supported", rules.required.len(), MAX_REQUIRED));
+    }
+
+    if let (Some(min), Some(max)) = (rules.min_length, rules.max_length) {
+        if min > max {
+            return Err(format!("minlength {} is larger than maxlength {}", min, max));
+        }
+    }
+    if rules.allowed.len() < 2 {
+        return Err("the rules allow fewer than 2 characters".to_string());
+    }
+
+    Ok(rules)
+}
+
+
+impl PasswordRules {
+    /* True if the password meets the required classes and the max-consecutive rule */
+    pub fn satisfies(&self, password: &[char]) -> bool {
+        if !self.required.iter().all(|class| password.iter().any(|c| class.contains(c))) {
+            return false;
+        }
+        if let Some(max) = self.max_consecutive {
+            let mut run = 0;
+            for (i, c) in password.iter().enumerate() {
+                run = if i > 0 && password[i - 1] == *c { run + 1 } else { 1 };
+                if run > max {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
+    /* Number of valid passwords of the given length.
+       Inclusion-exclusion over the required classes: strings avoiding every class of a subset are
+       strings over the remaining characters, counted with the max-consecutive limit.
+    */
+    pub fn count_valid(&self, length: usize) -> BigUint {
+        let mut positive = BigUint::zero();
+        let mut negative = BigUint::zero();
+
+        for subset in 0u32..(1 << self.required.len()) {
+            let remaining = self.allowed.iter()
+                .filter(|c| !self.required.iter().enumerate().any(|(i, class)| subset & (1 << i) != 0 && class.contains(c)))
+                .count();
+            let count = count_with_max_run(remaining, length, self.max_consecutive);
+            if subset.count_ones() % 2 == 0 {
+                positive += count;
+            } else {
+                negative += count;
+            }
+        }
+
+        positive - negative
+    }
+}
+
+/* Strings of the given length over an alphabet of size m with no run of identical characters longer than max_run.
+   runs[i] counts the strings of length i, the last run ending at i: the run before it is 1..=max_run long,
+   and each new run must use a different character than the previous one.
+*/
+fn count_with_max_run(m: usize, length: usize, max_run: Option<usize>) -> BigUint {
+    if length == 0 {
+        return BigUint::one();
+    }
+    let max_run = match max_run {
+        Some(max_run) => max_run,
+        None => return BigUint::from(m).pow(length as u32),
+    };
+
+    let mut runs: Vec<BigUint> = vec![BigUint::zero(); length + 1];
+    for i in 1..=length {
+        let mut total = BigUint::zero();
+        for r in 1..=max_run.min(i) {
+            if i == r {
+                total += BigUint::from(m);
+            } else if m > 0 {
+                total += &runs[i - r] * BigUint::from(m - 1);
+            }
+        }
+        runs[i] = total;
+    }
+
+    runs[length].clone()
+}
@@ -0,0 +1,142 @@
+/* patterns.rs
+
+   Weak pattern filter for --no-patterns: runs of one character ("aaa"), ascending or descending
+   sequences ("1234", "fedc"), walks along a QWERTY or QWERTZ key row ("qwert", "lkjh") and
+   words of the built-in wordlists inside the password.
+
+   Passwords with a pattern are thrown away as a whole. The fraction of passwords thrown away is
+   estimated by sampling, and log2 of the accepted fraction is subtracted from the entropy.
+*/
+
+use std::collections::HashSet;
+
+use crate::alphabet;
+use crate::bip39_wordlists;
+use crate::layout;
+
+pub const DEFAULT_PATTERNS: &str = "repeat:3,sequence:4,keyboard:4";
+
+pub struct PatternFilter {
+    repeat: Option<usize>,
+    sequence: Option<usize>,
+    keyboard: Option<usize>,
+    dictionary: Option<usize>,
+    keyboard_rows: Vec<Vec<char>>,
+    words: HashSet<String>,
+    longest_word: usize,
+}
+
+/* Parses "repeat:3,sequence:4,keyboard:4,dictionary:5". The number is the shortest rejected pattern. */
+pub fn parse_patterns(spec: &str) -> Result<PatternFilter, String> {
+    let mut filter = PatternFilter {
+        repeat: None,
+        sequence: None,
+        keyboard: None,
+        dictionary: None,
+        keyboard_rows: Vec::new(),
+        words: HashSet::new(),
+        longest_word: 0,
+    };
+
+    for item in spec.split(',').map(|item| item.trim()).filter(|item| !item.is_empty()) {
+        let (name, length) = match item.split_once(':') {
+            Some((name, length)) => match length.parse::<usize>() {
+                Ok(length) if length >= 2 => (name, Some(length)),
+                _ => return Err(format!("invalid length in \"{}\", at least 2 is needed", item)),
+            },
+            None => (item, None),
+        };
+        match name {
+            "repeat" => filter.repeat = Some(length.unwrap_or(3)),
+            "sequence" => filter.sequence = Some(length.unwrap_or(4)),
+            "keyboard" => filter.keyboard = Some(length.unwrap_or(4)),
+            "dictionary" => filter.dictionary = Some(length.unwrap_or(5)),
+            _ => return Err(format!("unknown pattern \"{}\", use repeat, sequence, keyboard or dictionary", name)),
+        }
+    }
+
+    if filter.keyboard.is_some() {
+        for name in ["us", "de"] {
+            for row in layout::get_layout(name).unwrap().rows() {
+                filter.keyboard_rows.push(row.chars().collect());
+            }
+        }
+    }
+
+    if let Some(min_length) = filter.dictionary {
+        let mut words: Vec<String> = alphabet::alphabet_get_elements("words-fi").unwrap();
+        words.extend(alphabet::alphabet_get_elements("assembly").unwrap());
+        words.extend(bip39_wordlists::BIP39_ENGLISH.iter().map(|word| word.to_string()));
+        filter.words = words.into_iter()
+            .map(|word| word.to_lowercase())
+            .filter(|word| word.chars().count() >= min_length)
+            .collect();
+        filter.longest_word = filter.words.iter().map(|word| word.chars().count()).max().unwrap_or(0);
+    }
+
+    Ok(filter)
+}
+
+
+impl PatternFilter {
+    /* The first weak pattern in the password, if any */
+    pub fn find(&self, password: &str) -> Option<String> {
+        let chars: Vec<char> = password.to_lowercase().chars().collect();
+
+        if let Some(length) = self.repeat {
+            for window in chars.windows(length) {
+                if window.iter().all(|c| *c == window[0]) {
+                    return Some(format!("repeat \"{}\"", window.iter().collect::<String>()));
+                }
+            }
+        }
+
+        if let Some(length) = self.sequence {
+            for window in chars.windows(length) {
+                let alphanumeric = window.iter().all(|c| c.is_ascii_alphanumeric());
+                let step = |d: i32| window.windows(2).all(|pair| pair[1] as i32 - pair[0] as i32 == d);
+                if alphanumeric && (step(1) || step(-1)) {
+                    return Some(format!("sequence \"{}\"", window.iter().collect::<String>()));
+                }
+            }
+        }
+
+        if let Some(length) = self.keyboard {
+            for window in chars.windows(length) {
+                let on_row = |row: &Vec<char>| {
+                    row.windows(length).any(|keys| !keys.contains(&' ') && (keys == window || keys.iter().rev().eq(window.iter())))
+                };
+                if self.keyboard_rows.iter().any(on_row) {
+                    return Some(format!("keyboard walk \"{}\"", window.iter().collect::<String>()));
+                }
+            }
+        }
+
+        if let Some(min_length) = self.dictionary {
+            for start in 0..chars.len() {
+                for end in (start + min_length)..=chars.len().min(start + self.longest_word) {
+                    let candidate: String = chars[start..end].iter().collect();
+                    if self.words.contains(&candidate) {
+                        return Some(format!("word \"{}\"", candidate));
+                    }
+                }
+            }
+        }
+
+        None
+    }
+
+    pub fn accepts(&self, password: &str) -> bool {
+        self.find(password).is_none()
+    }
+}
+
+/* Estimates the fraction of passwords the filter accepts from samples made by the closure.
+   Returns a lower bound, the estimate minus two standard errors, so the entropy is not overstated.
+*/
+pub fn estimate_accepted<F: FnMut() -> String>(filter: &PatternFilter, samples: usize, mut sample: F) -> f64 {
+    let accepted = (0..samples).filter(|_| filter.accepts(&sample())).count();
+    let p = accepted as f64 / samples as f64;
+    let standard_error = (p * (1.0 - p) / samples as f64).sqrt();
+    (p - 2.0 * standard_error).max(0.0)
+}
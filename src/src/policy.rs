@@ -0,0 +1,162 @@
+/* policy.rs
+
+   Password composition policies like "at least one upper, one lower, one digit and one symbol".
+   Passwords that do not meet the policy are thrown away as a whole and generated again,
+   so the output is uniform over the passwords that do. The entropy is then log2 of the
+   number of valid passwords, which is counted exactly.
+*/
+
+use num_bigint::BigUint;
+use num_traits::{One, ToPrimitive, Zero};
+
+use crate::normalize;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CharClass {
+    Upper,
+    Lower,
+    Digit,
+    Symbol,
+    Other,
+}
+
+pub const CHAR_CLASSES: [CharClass; 5] = [CharClass::Upper, CharClass::Lower, CharClass::Digit, CharClass::Symbol, CharClass::Other];
+
+impl CharClass {
+    pub fn of(c: char) -> CharClass {
+        if c.is_ascii_digit() {
+            CharClass::Digit
+        } else if c.is_uppercase() {
+            CharClass::Upper
+        } else if c.is_lowercase() {
+            CharClass::Lower
+        } else if !c.is_alphanumeric() {
+            CharClass::Symbol
+        } else {
+            CharClass::Other
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            CharClass::Upper => "upper",
+            CharClass::Lower => "lower",
+            CharClass::Digit => "digit",
+            CharClass::Symbol => "symbol",
+            CharClass::Other => "other",
+        }
+    }
+}
+
+pub struct Requirement {
+    pub class: CharClass,
+    pub min: usize,
+}
+
+
+/* Parses "upper,lower:2,digit,symbol" into requirements. The count after ':' defaults to 1. */
+pub fn parse_requirements(spec: &str) -> Result<Vec<Requirement>, String> {
+    let mut requirements: Vec<Requirement> = Vec::new();
+
+    for item in spec.split(',').map(|item| item.trim()).filter(|item| !item.is_empty()) {
+        let (name, min) = match item.split_once(':') {
+            Some((name, min)) => (name, min.parse::<usize>().map_err(|_| format!("invalid count in \"{}\"", item))?),
+            None => (item, 1),
+        };
+
+        let class = match name {
+            "upper" => CharClass::Upper,
+            "lower" => CharClass::Lower,
+            "digit" => CharClass::Digit,
+            "symbol" => CharClass::Symbol,
+            _ => return Err(format!("unknown character class \"{}\", use upper, lower, digit or symbol", name)),
+        };
+
+        match requirements.iter_mut().find(|r| r.class == class) {
+            Some(existing) => existing.min = existing.min.max(min),
+            None => requirements.push(Requirement { class, min }),
+        }
+    }
+
+    Ok(requirements)
+}
+
+fn required_min(requirements: &[Requirement], class: CharClass) -> usize {
+    requirements.iter().find(|r| r.class == class).map(|r| r.min).unwrap_or(0)
+}
+
+
+/* Checks the requirements are usable with the alphabet: single characters, and every required class present */
+pub fn check_alphabet(elements: &[String], requirements: &[Requirement]) -> Result<(), String> {
+    if elements.iter().any(|element| normalize::grapheme_count(element) != 1) {
+        return Err("character class requirements need an alphabet of single characters".to_string());
+    }
+    for requirement in requirements {
+        if requirement.min > 0 && !elements.iter().any(|element| CharClass::of(element.chars().next().unwrap()) == requirement.class) {
+            return Err(format!("the alphabet has no {} characters", requirement.class.name()));
+        }
+    }
+    Ok(())
+}
+
+/* True if the picked elements meet every requirement */
+pub fn satisfies(elements: &[String], indices: &[usize], requirements: &[Requirement]) -> bool {
+    requirements.iter().all(|requirement| {
+        let count = indices.iter()
+            .filter(|&&index| CharClass::of(elements[index].chars().next().unwrap()) == requirement.class)
+            .count();
+        count >= requirement.min
+    })
+}
+
+
+/* Number of passwords of the given length over the alphabet that meet the requirements.
+   Each class is added in turn: f[j] is the number of ways to fill j positions with the classes so far,
+   and adding k characters of a class with n members gives C(j + k, k) * n^k new arrangements.
+*/
+pub fn count_valid(elements: &[String], length: usize, requirements: &[Requirement]) -> BigUint {
+    let mut f: Vec<BigUint> = vec![BigUint::zero(); length + 1];
+    f[0] = BigUint::one();
+
+    for class in CHAR_CLASSES {
+        let class_size = elements.iter().filter(|element| CharClass::of(element.chars().next().unwrap()) == class).count();
+        let min = required_min(requirements, class);
+
+        let mut next: Vec<BigUint> = vec![BigUint::zero(); length + 1];
+        for j in 0..=length {
+            if f[j].is_zero() {
+                continue;
+            }
+            for k in min..=(length - j) {
+                if class_size == 0 && k > 0 {
+                    break;
+                }
+                next[j + k] += &f[j] * binomial(j + k, k) * BigUint::from(class_size).pow(k as u32);
+            }
+        }
+        f = next;
+    }
+
+    f[length].clone()
+}
+
+fn binomial(n: usize, k: usize) -> BigUint {
+    let mut result = BigUint::one();
+    for i in 0..k {
+        result = result * BigUint::from(n - i) / BigUint::from(i + 1);
+    }
+    result
+}
+
+/* log2 of a big number, from its top 64 bits */
+pub fn log2_big(n: &BigUint) -> f64 {
+    if n.is_zero() {
+        return f64::NEG_INFINITY;
+    }
+    let bits = n.bits();
+    if bits <= 64 {
+        return n.to_f64().unwrap().log2();
+    }
+    let shift = bits - 64;
+    (n >> shift).to_f64().unwrap().log2() + shift as f64
+}
@@ -0,0 +1,897 @@
+/* qr.rs
+
+   QR code encoder for moving secrets to phones and offline machines without retyping them.
+   Byte mode only, versions 1 to 40, error correction L, M, Q or H, following ISO/IEC 18004
+   the same way as Project Nayuki's QR Code generator: the smallest version that fits is used
+   and the mask with the lowest penalty is chosen.
+
+   Rendering: Unicode half blocks for the terminal, SVG, and 1 bit grayscale PNG written with
+   stored (uncompressed) deflate blocks, so no compression library is needed.
+
+   Payloads: the plain secret, a WIFI:T:WPA;S:...;P:...;; network, or an otpauth:// URI for
+   TOTP apps, which needs a base32 secret.
+*/
+
+use std::str::FromStr;
+
+use zeroize::Zeroize;
+
+/* Light modules around the code, as the standard asks */
+const QUIET_ZONE: usize = 4;
+/* PNG pixels per module */
+const PNG_SCALE: usize = 8;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ErrorCorrection {
+    Low,
+    Medium,
+    Quartile,
+    High,
+}
+
+impl FromStr for ErrorCorrection {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "L" | "l" => Ok(ErrorCorrection::Low),
+            "M" | "m" => Ok(ErrorCorrection::Medium),
+            "Q" | "q" => Ok(ErrorCorrection::Quartile),
+            "H" | "h" => Ok(ErrorCorrection::High),
+            _ => Err(()),
+        }
+    }
+}
+
+impl ErrorCorrection {
+    fn ordinal(self) -> usize {
+        match self {
+            ErrorCorrection::Low => 0,
+            ErrorCorrection::Medium => 1,
+            ErrorCorrection::Quartile => 2,
+            ErrorCorrection::High => 3,
+        }
+    }
+
+    fn format_bits(self) -> u32 {
+        match self {
+            ErrorCorrection::Low => 1,
+            ErrorCorrection::Medium => 0,
+            ErrorCorrection::Quartile => 3,
+            ErrorCorrection::High => 2,
+        }
+    }
+}
+
+/* Error correction codewords per block, by error correction level and version (index 0 unused) */
+const ECC_CODEWORDS_PER_BLOCK: [[u8; 41]; 4] = [
+    [0, 7, 10, 15, 20, 26, 18, 20, 24, 30, 18, 20, 24, 26, 30, 22, 24, 28, 30, 28, 28, 28, 28, 30, 30, 26, 28, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30],
+    [0, 10, 16, 26, 18, 24, 16, 18, 22, 22, 26, 30, 22, 22, 24, 24, 28, 28, 26, 26, 26, 26, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28],
+    [0, 13, 22, 18, 26, 18, 24, 18, 22, 20, 24, 28, 26, 24, 20, 30, 24, 28, 28, 26, 30, 28, 30, 30, 30, 30, 28, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30],
+    [0, 17, 28, 22, 16, 22, 28, 26, 26, 24, 28, 24, 28, 22, 24, 24, 30, 28, 28, 26, 28, 30, 24, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30],
+];
+
+/* Error correction blocks, by error correction level and version (index 0 unused) */
+const NUM_ERROR_CORRECTION_BLOCKS: [[u8; 41]; 4] = [
+    [0, 1, 1, 1, 1, 1, 2, 2, 2, 2, 4, 4, 4, 4, 4, 6, 6, 6, 6, 7, 8, 8, 9, 9, 10, 12, 12, 12, 13, 14, 15, 16, 17, 18, 19, 19, 20, 21, 22, 24, 25],
+    [0, 1, 1, 1, 2, 2, 4, 4, 4, 5, 5, 5, 8, 9, 9, 10, 10, 11, 13, 14, 16, 17, 17, 18, 20, 21, 23, 25, 26, 28, 29, 31, 33, 35, 37, 38, 40, 43, 45, 47, 49],
+    [0, 1, 1, 2, 2, 4, 4, 6, 6, 8, 8, 8, 10, 12, 16, 12, 17, 16, 18, 21, 20, 23, 23, 25, 27, 29, 34, 34, 35, 38, 40, 43, 45, 48, 51, 53, 56, 59, 62, 65, 68],
+    [0, 1, 1, 2, 4, 4, 4, 5, 6, 8, 8, 11, 11, 16, 16, 18, 16, 19, 21, 25, 25, 25, 34, 30, 32, 35, 37, 40, 42, 45, 48, 51, 54, 57, 60, 63, 66, 70, 74, 77, 81],
+];
+
+/* Penalty weights for choosing the mask */
+const PENALTY_N1: i32 = 3;
+const PENALTY_N2: i32 = 3;
+const PENALTY_N3: i32 = 40;
+const PENALTY_N4: i32 = 10;
+
+pub struct QrCode {
+    size: usize,
+    modules: Vec<bool>,
+    is_function: Vec<bool>,
+}
+
+/* Modules left for data and error correction after the function patterns */
+fn num_raw_data_modules(version: usize) -> usize {
+    let mut result = (16 * version + 128) * version + 64;
+    if version >= 2 {
+        let num_align = version / 7 + 2;
+        result -= (25 * num_align - 10) * num_align - 55;
+        if version >= 7 {
+            result -= 36;
+        }
+    }
+    result
+}
+
+fn num_data_codewords(version: usize, ec: ErrorCorrection) -> usize {
+    num_raw_data_modules(version) / 8
+        - ECC_CODEWORDS_PER_BLOCK[ec.ordinal()][version] as usize * NUM_ERROR_CORRECTION_BLOCKS[ec.ordinal()][version] as usize
+}
+
+fn get_bit(value: u32, i: usize) -> bool {
+    (value >> i) & 1 != 0
+}
+
+/* Multiplication in GF(2^8) modulo x^8 + x^4 + x^3 + x^2 + 1 */
+fn gf_multiply(x: u8, y: u8) -> u8 {
+    let mut z: u32 = 0;
+    for i in (0..8).rev() {
+        z = (z << 1) ^ ((z >> 7) * 0x11d);
+        z ^= ((y as u32 >> i) & 1) * x as u32;
+    }
+    z as u8
+}
+
+/* Reed-Solomon generator polynomial of the degree, highest coefficient left out */
+fn reed_solomon_divisor(degree: usize) -> Vec<u8> {
+    let mut result = vec![0u8; degree];
+    result[degree - 1] = 1;
+    let mut root: u8 = 1;
+    for _ in 0..degree {
+        for j in 0..degree {
+            result[j] = gf_multiply(result[j], root);
+            if j + 1 < degree {
+                result[j] ^= result[j + 1];
+            }
+        }
+        root = gf_multiply(root, 0x02);
+    }
+    result
+}
+
+fn reed_solomon_remainder(data: &[u8], divisor: &[u8]) -> Vec<u8> {
+    let mut result = vec![0u8; divisor.len()];
+    for &b in data {
+        let factor = b ^ result.remove(0);
+        result.push(0);
+        for (x, &y) in result.iter_mut().zip(divisor) {
+            *x ^= gf_multiply(y, factor);
+        }
+    }
+    result
+}
+
+/* Splits the data into blocks, adds the error correction to each and interleaves them */
+fn add_ecc_and_interleave(data: &[u8], version: usize, ec: ErrorCorrection) -> Vec<u8> {
+    let num_blocks = NUM_ERROR_CORRECTION_BLOCKS[ec.ordinal()][version] as usize;
+    let block_ecc_len = ECC_CODEWORDS_PER_BLOCK[ec.ordinal()][version] as usize;
+    let raw_codewords = num_raw_data_modules(version) / 8;
+    let num_short_blocks = num_blocks - raw_codewords % num_blocks;
+    let short_block_len = raw_codewords / num_blocks;
+
+    let divisor = reed_solomon_divisor(block_ecc_len);
+    let mut blocks: Vec<Vec<u8>> = Vec::with_capacity(num_blocks);
+    let mut k = 0;
+    for i in 0..num_blocks {
+        let data_len = short_block_len - block_ecc_len + usize::from(i >= num_short_blocks);
+        let mut block = data[k..k + data_len].to_vec();
+        k += data_len;
+        let ecc = reed_solomon_remainder(&block, &divisor);
+        // Short blocks get a placeholder so that all blocks have the same length
+        if i < num_short_blocks {
+            block.push(0);
+        }
+        block.extend_from_slice(&ecc);
+        blocks.push(block);
+    }
+
+    let mut result = Vec::with_capacity(raw_codewords);
+    for i in 0..blocks[0].len() {
+        for (j, block) in blocks.iter().enumerate() {
+            if i != short_block_len - block_ecc_len || j >= num_short_blocks {
+                result.push(block[i]);
+            }
+        }
+    }
+    for block in blocks.iter_mut() {
+        block.zeroize();
+    }
+    result
+}
+
+/* Adds the low count bits of value to the bit buffer, most significant first */
+fn append_bits(bits: &mut Vec<bool>, value: u32, count: usize) {
+    for i in (0..count).rev() {
+        bits.push(get_bit(value, i));
+    }
+}
+
+impl QrCode {
+    /* Encodes the bytes in byte mode with the smallest version that fits */
+    pub fn encode_bytes(data: &[u8], ec: ErrorCorrection) -> Result<QrCode, String> {
+        let count_bits = |version: usize| if version <= 9 { 8 } else { 16 };
+        let version = (1..=40)
+            .find(|&version| 4 + count_bits(version) + data.len() * 8 <= num_data_codewords(version, ec) * 8)
+            .ok_or_else(|| format!("{} bytes do not fit in a QR code with this error correction level", data.len()))?;
+        let capacity_bits = num_data_codewords(version, ec) * 8;
+
+        let mut bits: Vec<bool> = Vec::with_capacity(capacity_bits);
+        append_bits(&mut bits, 0x4, 4);
+        append_bits(&mut bits, data.len() as u32, count_bits(version));
+        for &b in data {
+            append_bits(&mut bits, b as u32, 8);
+        }
+        // Terminator, byte alignment, then alternating pad bytes
+        let terminator = (capacity_bits - bits.len()).min(4);
+        append_bits(&mut bits, 0, terminator);
+        let alignment = (8 - bits.len() % 8) % 8;
+        append_bits(&mut bits, 0, alignment);
+        for &pad in [0xecu32, 0x11].iter().cycle() {
+            if bits.len() >= capacity_bits {
+                break;
+            }
+            append_bits(&mut bits, pad, 8);
+        }
+
+        let mut codewords: Vec<u8> = bits.chunks(8)
+            .map(|byte| byte.iter().fold(0u8, |acc, &bit| (acc << 1) | u8::from(bit)))
+            .collect();
+        bits.iter_mut().for_each(|bit| *bit = false);
+
+        let mut all_codewords = add_ecc_and_interleave(&codewords, version, ec);
+        codewords.zeroize();
+
+        let size = version * 4 + 17;
+        let mut qr = QrCode {
+            size,
+            modules: vec![false; size * size],
+            is_function: vec![false; size * size],
+        };
+        qr.draw_function_patterns(version, ec);
+        qr.draw_codewords(&all_codewords);
+        all_codewords.zeroize();
+
+        let mut best_mask = 0;
+        let mut min_penalty = i32::MAX;
+        for mask in 0..8 {
+            qr.apply_mask(mask);
+            qr.draw_format_bits(ec, mask);
+            let penalty = qr.penalty_score();
+            if penalty < min_penalty {
+                best_mask = mask;
+                min_penalty = penalty;
+            }
+            // Masking twice undoes it
+            qr.apply_mask(mask);
+        }
+        qr.apply_mask(best_mask);
+        qr.draw_format_bits(ec, best_mask);
+
+        Ok(qr)
+    }
+
+    /* Whether the module at column x, row y is dark */
+    pub fn module(&self, x: usize, y: usize) -> bool {
+        self.modules[y * self.size + x]
+    }
+
+    fn set_function_module(&mut self, x: usize, y: usize, dark: bool) {
+        self.modules[y * self.size + x] = dark;
+        self.is_function[y * self.size + x] = true;
+    }
+
+    fn alignment_pattern_positions(&self, version: usize) -> Vec<usize> {
+        if version == 1 {
+            return Vec::new();
+        }
+        let num_align = version / 7 + 2;
+        let step = (version * 8 + num_align * 3 + 5) / (num_align * 4 - 4) * 2;
+        let mut result: Vec<usize> = (0..num_align - 1).map(|i| self.size - 7 - i * step).collect();
+        result.push(6);
+        result.reverse();
+        result
+    }
+
+    fn draw_function_patterns(&mut self, version: usize, ec: ErrorCorrection) {
+        let size = self.size;
+        for i in 0..size {
+            self.set_function_module(6, i, i % 2 == 0);
+            self.set_function_module(i, 6, i % 2 == 0);
+        }
+
+        for (x, y) in [(3, 3), (size - 4, 3), (3, size - 4)] {
+            self.draw_finder_pattern(x as i32, y as i32);
+        }
+
+        let positions = self.alignment_pattern_positions(version);
+        let num_align = positions.len();
+        for i in 0..num_align {
+            for j in 0..num_align {
+                // The finder patterns take these corners
+                let finder_corner = (i == 0 && (j == 0 || j == num_align - 1)) || (i == num_align - 1 && j == 0);
+                if !finder_corner {
+                    self.draw_alignment_pattern(positions[i], positions[j]);
+                }
+            }
+        }
+
+        // Placeholder format bits, so that the area is marked as function modules
+        self.draw_format_bits(ec, 0);
+        self.draw_version(version);
+    }
+
+    /* Finder pattern with its separator, centered at (x, y), clipped at the edges */
+    fn draw_finder_pattern(&mut self, x: i32, y: i32) {
+        for dy in -4..=4i32 {
+            for dx in -4..=4i32 {
+                let (xx, yy) = (x + dx, y + dy);
+                if (0..self.size as i32).contains(&xx) && (0..self.size as i32).contains(&yy) {
+                    let distance = dx.abs().max(dy.abs());
+                    self.set_function_module(xx as usize, yy as usize, distance != 2 && distance != 4);
+                }
+            }
+        }
+    }
+
+    fn draw_alignment_pattern(&mut self, x: usize, y: usize) {
+        for dy in -2..=2i32 {
+            for dx in -2..=2i32 {
+                self.set_function_module((x as i32 + dx) as usize, (y as i32 + dy) as usize, dx.abs().max(dy.abs()) != 1);
+            }
+        }
+    }
+
+    fn draw_format_bits(&mut self, ec: ErrorCorrection, mask: u32) {
+        let data = ec.format_bits() << 3 | mask;
+        let mut remainder = data;
+        for _ in 0..10 {
+            remainder = (remainder << 1) ^ ((remainder >> 9) * 0x537);
+        }
+        let bits = (data << 10 | remainder) ^ 0x5412;
+        let size = self.size;
+
+        // First copy, around the top left finder pattern
+        for i in 0..6 {
+            self.set_function_module(8, i, get_bit(bits, i));
+        }
+        self.set_function_module(8, 7, get_bit(bits, 6));
+        self.set_function_module(8, 8, get_bit(bits, 7));
+        self.set_function_module(7, 8, get_bit(bits, 8));
+        for i in 9..15 {
+            self.set_function_module(14 - i, 8, get_bit(bits, i));
+        }
+
+        // Second copy, split between the other two finder patterns
+        for i in 0..8 {
+            self.set_function_module(size - 1 - i, 8, get_bit(bits, i));
+        }
+        for i in 8..15 {
+            self.set_function_module(8, size - 15 + i, get_bit(bits, i));
+        }
+        self.set_function_module(8, size - 8, true);
+    }
+
+    fn draw_version(&mut self, version: usize) {
+        if version < 7 {
+            return;
+        }
+        let mut remainder = version as u32;
+        for _ in 0..12 {
+            remainder = (remainder << 1) ^ ((remainder >> 11) * 0x1f25);
+        }
+        let bits = (version as u32) << 12 | remainder;
+
+        for i in 0..18 {
+            let bit = get_bit(bits, i);
+            let a = self.size - 11 + i % 3;
+            let b = i / 3;
+            self.set_function_module(a, b, bit);
+            self.set_function_module(b, a, bit);
+        }
+    }
+
+    /* Places the codewords in the zigzag order, two columns at a time from the bottom right */
+    fn draw_codewords(&mut self, data: &[u8]) {
+        let size = self.size as i32;
+        let mut i = 0;
+        let mut right = size - 1;
+        while right >= 1 {
+            if right == 6 {
+                right = 5;
+            }
+            for vertical in 0..size {
+                for j in 0..2 {
+                    let x = (right - j) as usize;
+                    let upward = (right + 1) & 2 == 0;
+                    let y = (if upward { size - 1 - vertical } else { vertical }) as usize;
+                    if !self.is_function[y * self.size + x] && i < data.len() * 8 {
+                        self.modules[y * self.size + x] = get_bit(data[i >> 3] as u32, 7 - (i & 7));
+                        i += 1;
+                    }
+                }
+            }
+            right -= 2;
+        }
+    }
+
+    fn apply_mask(&mut self, mask: u32) {
+        for y in 0..self.size {
+            for x in 0..self.size {
+                let invert = match mask {
+                    0 => (x + y) % 2 == 0,
+                    1 => y % 2 == 0,
+                    2 => x % 3 == 0,
+                    3 => (x + y) % 3 == 0,
+                    4 => (x / 3 + y / 2) % 2 == 0,
+                    5 => x * y % 2 + x * y % 3 == 0,
+                    6 => (x * y % 2 + x * y % 3) % 2 == 0,
+                    _ => ((x + y) % 2 + x * y % 3) % 2 == 0,
+                };
+                if invert && !self.is_function[y * self.size + x] {
+                    self.modules[y * self.size + x] ^= true;
+                }
+            }
+        }
+    }
+
+    fn penalty_score(&self) -> i32 {
+        let size = self.size;
+        let mut result = 0;
+
+        // Runs of five or more and finder-like patterns, in rows and in columns
+        for transpose in [false, true] {
+            for a in 0..size {
+                let get = |b: usize| if transpose { self.module(a, b) } else { self.module(b, a) };
+                let mut run_color = false;
+                let mut run_length = 0;
+                let mut history = FinderPenalty::new(size as i32);
+                for b in 0..size {
+                    if get(b) == run_color {
+                        run_length += 1;
+                        if run_length == 5 {
+                            result += PENALTY_N1;
+                        } else if run_length > 5 {
+                            result += 1;
+                        }
+                    } else {
+                        history.add_history(run_length);
+                        if !run_color {
+                            result += history.count_patterns() * PENALTY_N3;
+                        }
+                        run_color = get(b);
+                        run_length = 1;
+                    }
+                }
+                result += history.terminate_and_count(run_color, run_length) * PENALTY_N3;
+            }
+        }
+
+        // 2x2 blocks of one color
+        for y in 0..size - 1 {
+            for x in 0..size - 1 {
+                let color = self.module(x, y);
+                if color == self.module(x + 1, y) && color == self.module(x, y + 1) && color == self.module(x + 1, y + 1) {
+                    result += PENALTY_N2;
+                }
+            }
+        }
+
+        // Balance of dark and light modules
+        let dark = self.modules.iter().filter(|&&dark| dark).count() as i32;
+        let total = (size * size) as i32;
+        let k = ((dark * 20 - total * 10).abs() + total - 1) / total - 1;
+        result + k * PENALTY_N4
+    }
+
+    /* Two module rows per line with half blocks. Light modules are drawn, for light text on a dark background. */
+    pub fn to_terminal(&self) -> String {
+        let full = self.size + 2 * QUIET_ZONE;
+        let light = |x: usize, y: usize| {
+            x < QUIET_ZONE || y < QUIET_ZONE || x >= QUIET_ZONE + self.size || y >= QUIET_ZONE + self.size
+                || !self.module(x - QUIET_ZONE, y - QUIET_ZONE)
+        };
+
+        let mut output = String::new();
+        for y in (0..full).step_by(2) {
+            for x in 0..full {
+                let top = light(x, y);
+                let bottom = y + 1 >= full || light(x, y + 1);
+                output.push(match (top, bottom) {
+                    (true, true) => '█',
+                    (true, false) => '▀',
+                    (false, true) => '▄',
+                    (false, false) => ' ',
+                });
+            }
+            output.push('\n');
+        }
+        output
+    }
+
+    pub fn to_svg(&self) -> String {
+        let full = self.size + 2 * QUIET_ZONE;
+        let mut path = String::new();
+        for y in 0..self.size {
+            for x in 0..self.size {
+                if self.module(x, y) {
+                    path.push_str(&format!("M{},{}h1v1h-1z", x + QUIET_ZONE, y + QUIET_ZONE));
+                }
+            }
+        }
+        let svg = format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<svg xmlns=\"http://www.w3.org/2000/svg\" version=\"1.1\" viewBox=\"0 0 {0} {0}\" stroke=\"none\" shape-rendering=\"crispEdges\">\n<rect width=\"100%\" height=\"100%\" fill=\"#ffffff\"/>\n<path d=\"{1}\" fill=\"#000000\"/>\n</svg>\n",
+            full, path);
+        path.zeroize();
+        svg
+    }
+
+    /* 1 bit grayscale PNG, PNG_SCALE pixels per module */
+    pub fn to_png(&self) -> Vec<u8> {
+        let width = (self.size + 2 * QUIET_ZONE) * PNG_SCALE;
+        let row_bytes = width.div_ceil(8);
+
+        // Scanlines, each with filter type 0. A set bit is white.
+        let mut raw = Vec::with_capacity((row_bytes + 1) * width);
+        for py in 0..width {
+            raw.push(0);
+            let mut row = vec![0u8; row_bytes];
+            for px in 0..width {
+                let (x, y) = (px / PNG_SCALE, py / PNG_SCALE);
+                let dark = x >= QUIET_ZONE && y >= QUIET_ZONE && x < QUIET_ZONE + self.size && y < QUIET_ZONE + self.size
+                    && self.module(x - QUIET_ZONE, y - QUIET_ZONE);
+                if !dark {
+                    row[px / 8] |= 0x80 >> (px % 8);
+                }
+            }
+            raw.extend_from_slice(&row);
+        }
+
+        let mut header = Vec::with_capacity(13);
+        header.extend_from_slice(&(width as u32).to_be_bytes());
+        header.extend_from_slice(&(width as u32).to_be_bytes());
+        header.extend_from_slice(&[1, 0, 0, 0, 0]);
+
+        let mut png = vec![0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a];
+        png_chunk(&mut png, b"IHDR", &header);
+        let mut compressed = zlib_stored(&raw);
+        png_chunk(&mut png, b"IDAT", &compressed);
+        png_chunk(&mut png, b"IEND", &[]);
+        raw.zeroize();
+        compressed.zeroize();
+        png
+    }
+}
+
+/* Run history for finding 1:1:3:1:1 patterns with four light modules on either side */
+struct FinderPenalty {
+    qr_size: i32,
+    run_history: [i32; 7],
+}
+
+impl FinderPenalty {
+    fn new(qr_size: i32) -> Self {
+        FinderPenalty { qr_size, run_history: [0; 7] }
+    }
+
+    fn add_history(&mut self, mut run_length: i32) {
+        // The light border counts as part of the first run
+        if self.run_history[0] == 0 {
+            run_length += self.qr_size;
+        }
+        self.run_history.copy_within(0..6, 1);
+        self.run_history[0] = run_length;
+    }
+
+    fn count_patterns(&self) -> i32 {
+        let history = &self.run_history;
+        let n = history[1];
+        let core = n > 0 && history[2] == n && history[3] == n * 3 && history[4] == n && history[5] == n;
+        i32::from(core && history[0] >= n * 4 && history[6] >= n) + i32::from(core && history[6] >= n * 4 && history[0] >= n)
+    }
+
+    fn terminate_and_count(mut self, run_color: bool, mut run_length: i32) -> i32 {
+        if run_color {
+            self.add_history(run_length);
+            run_length = 0;
+        }
+        // The light border after the last module
+        run_length += self.qr_size;
+        self.add_history(run_length);
+        self.count_patterns()
+    }
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffffffffu32;
+    for &b in data {
+        crc ^= b as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xedb88320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+fn png_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+/* zlib stream of stored deflate blocks */
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut output = vec![0x78, 0x01];
+    let mut chunks = data.chunks(65535).peekable();
+    if chunks.peek().is_none() {
+        output.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(chunk) = chunks.next() {
+        output.push(u8::from(chunks.peek().is_none()));
+        output.extend_from_slice(&(chunk.len() as u16).to_le_bytes());
+        output.extend_from_slice(&(!(chunk.len() as u16)).to_le_bytes());
+        output.extend_from_slice(chunk);
+    }
+
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    output.extend_from_slice(&(b << 16 | a).to_be_bytes());
+    output
+}
+
+
+/* Backslash escapes for the WIFI: format */
+fn wifi_escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        if matches!(c, '\\' | ';' | ',' | '"' | ':') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/* Percent encoding of everything but the unreserved characters of RFC 3986 */
+fn percent_encode(s: &str) -> String {
+    let mut encoded = String::with_capacity(s.len());
+    for &b in s.as_bytes() {
+        if b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.' | b'_' | b'~') {
+            encoded.push(b as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", b));
+        }
+    }
+    encoded
+}
+
+pub enum Payload {
+    Text,
+    Wifi { ssid: String },
+    Otpauth { label: String, issuer: Option<String> },
+}
+
+impl Payload {
+    /* The string put into the QR code for this secret */
+    pub fn build(&self, secret: &str) -> Result<String, String> {
+        match self {
+            Payload::Text => Ok(secret.to_string()),
+            Payload::Wifi { ssid } => {
+                // WPA takes 8 to 63 printable ASCII characters, or the key itself as 64 hex digits
+                let length = secret.chars().count();
+                let raw_key = length == 64 && secret.chars().all(|c| c.is_ascii_hexdigit());
+                if !raw_key && !(8..=63).contains(&length) {
+                    return Err(format!("a WPA passphrase has 8 to 63 characters, this secret has {}", length));
+                }
+                if !secret.chars().all(|c| (' '..='~').contains(&c)) {
+                    return Err("a WPA passphrase can only have printable ASCII characters".to_string());
+                }
+                let mut password = wifi_escape(secret);
+                let payload = format!("WIFI:T:WPA;S:{};P:{};;", wifi_escape(ssid), password);
+                password.zeroize();
+                Ok(payload)
+            }
+            Payload::Otpauth { label, issuer } => {
+                // TOTP apps take the secret as unpadded base32
+                let mut base32: String = secret.chars().filter(|&c| c != '=').collect::<String>().to_uppercase();
+                if let Some(c) = base32.chars().find(|c| !matches!(c, 'A'..='Z' | '2'..='7')) {
+                    base32.zeroize();
+                    return Err(format!("otpauth needs a base32 secret, '{}' is not base32", c));
+                }
+                let mut payload = format!("otpauth://totp/{}?secret={}", percent_encode(label), base32);
+                if let Some(issuer) = issuer {
+                    payload.push_str(&format!("&issuer={}", percent_encode(issuer)));
+                }
+                base32.zeroize();
+                Ok(payload)
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /* Reference symbols from Nayuki's qrcodegen 1.8: one byte segment, smallest version, automatic mask,
+       no error correction boost. One row per line, '#' is dark.
+    */
+    const KEYGEN_L: &[&str] = &[
+        "#######...#.#.#######",
+        "#.....#.....#.#.....#",
+        "#.###.#.#.#...#.###.#",
+        "#.###.#.....#.#.###.#",
+        "#.###.#..#.##.#.###.#",
+        "#.....#..###..#.....#",
+        "#######.#.#.#.#######",
+        "........#.#..........",
+        "###.#####.#.###...#..",
+        ".####..#####.#.#...##",
+        "##.#..###.##.###.####",
+        "#.#..#.##..###.##...#",
+        "..###.#.####.###.#...",
+        "........#.....##.####",
+        "#######.###.#...#####",
+        "#.....#.##....###..##",
+        "#.###.#.#.#.#.##...##",
+        "#.###.#..#.#.#.##..#.",
+        "#.###.#.##.#.#####..#",
+        "#.....#.##.###.....#.",
+        "#######.##.#.####..##",
+    ];
+
+    const KEYGEN_M: &[&str] = &[
+        "#######.###...#######",
+        "#.....#.#.###.#.....#",
+        "#.###.#..#.##.#.###.#",
+        "#.###.#.###...#.###.#",
+        "#.###.#..##...#.###.#",
+        "#.....#..#....#.....#",
+        "#######.#.#.#.#######",
+        "........#.#..........",
+        "#.##.###......#..#.##",
+        "##...#.##.#.#..#.##.#",
+        ".##.#.##.#..#####..##",
+        ".###.#....#.##...#..#",
+        "...#..#.####.#..##..#",
+        "........###..#...##..",
+        "#######.#....##.##...",
+        "#.....#.#.#########.#",
+        "#.###.#...##..#######",
+        "#.###.#.##...#...#.#.",
+        "#.###.#.#.##.#...#...",
+        "#.....#....##.##....#",
+        "#######.##.##..##.#..",
+    ];
+
+    const KEYGEN_Q: &[&str] = &[
+        "#######..##...#######",
+        "#.....#..##.#.#.....#",
+        "#.###.#.#.#...#.###.#",
+        "#.###.#..###..#.###.#",
+        "#.###.#.##..#.#.###.#",
+        "#.....#.#.#...#.....#",
+        "#######.#.#.#.#######",
+        ".........####........",
+        ".#..#.#.#...##.##.#..",
+        "##.....#.###.##..###.",
+        "..######.#.###.....#.",
+        "##..##..###....#...##",
+        "....#.###.###.####.#.",
+        "........##.#.......#.",
+        "#######...##..###..#.",
+        "#.....#...#.####....#",
+        "#.###.#.###..####...#",
+        "#.###.#..##.###.#####",
+        "#.###.#..##.##..#.#..",
+        "#.....#.#####...#....",
+        "#######..###..##....#",
+    ];
+
+    const KEYGEN_H: &[&str] = &[
+        "#######..#..#.#######",
+        "#.....#.###.#.#.....#",
+        "#.###.#.##..#.#.###.#",
+        "#.###.#.#.###.#.###.#",
+        "#.###.#.##....#.###.#",
+        "#.....#.#.#...#.....#",
+        "#######.#.#.#.#######",
+        ".........####........",
+        "..#..####.#.##.#####.",
+        ".#.#....##.##.#..#..#",
+        "##.##.#.####.##...#.#",
+        "..####..##...##.##.##",
+        "#..#..#....##.#....#.",
+        "........#######...#.#",
+        "#######.#.###..##.#.#",
+        "#.....#.##.####.##..#",
+        "#.###.#...#.###..#..#",
+        "#.###.#..#......##...",
+        "#.###.#.#....##.#..##",
+        "#.....#...##...#.#...",
+        "#######....##.#.##..#",
+    ];
+
+    /* Version 7 at level M, so the version information is covered too. Rows as hex, first module in the high bit. */
+    const WIFI_V7_M: &[&str] = &[
+        "fefd104fcbf8",
+        "8295d1461208",
+        "ba3ab22592e8",
+        "ba983be51ae8",
+        "ba44bfadfae8",
+        "8279f8ab8208",
+        "feaaaaaaabf8",
+        "00bf68a90000",
+        "b73a7fc82a58",
+        "98ccb1de1d80",
+        "e7479d5f8eb8",
+        "d4117342e408",
+        "0b97f6a02a50",
+        "f05fefa90310",
+        "573be9725a50",
+        "d0d7c2ce8bf0",
+        "e2ec18b1c378",
+        "a088739db1d8",
+        "7f862cacf290",
+        "b4b53d1c43c0",
+        "cfc58fbc6ff0",
+        "98c048861888",
+        "7abf5ac02a98",
+        "c8cac884a8d8",
+        "0fbf3f871fa0",
+        "546b92718778",
+        "f634b9ff4860",
+        "c4d7e9f953e8",
+        "76234c93d788",
+        "b583b85c7488",
+        "12ed32e96040",
+        "39e854af7c88",
+        "5f650a8d6160",
+        "e493d3479d78",
+        "0a9809ceba38",
+        "794bb769ea18",
+        "9a3f8fe56fc0",
+        "00e0e8a15890",
+        "fe9b2ad81aa0",
+        "82c528dae8f8",
+        "ba468ff7efd0",
+        "bafe5a15ea08",
+        "baa1b0e41f30",
+        "824ae66bde48",
+        "fed2617803e0",
+    ];
+    const WIFI_V7_PAYLOAD: &str = "WIFI:T:WPA;S:keygen test network;P:r52SG1fUW7!SDArb.6T49f-correct-horse-battery-staple-0123456789abcdefghijklmno;;";
+
+    fn rows(code: &QrCode) -> Vec<String> {
+        (0..code.size).map(|y| (0..code.size).map(|x| if code.module(x, y) { '#' } else { '.' }).collect()).collect()
+    }
+
+    fn hex_rows(code: &QrCode) -> Vec<String> {
+        (0..code.size).map(|y| {
+            let mut bytes = vec![0u8; code.size.div_ceil(8)];
+            for x in 0..code.size {
+                if code.module(x, y) {
+                    bytes[x / 8] |= 0x80 >> (x % 8);
+                }
+            }
+            hex::encode(bytes)
+        }).collect()
+    }
+
+    #[test]
+    fn known_answer_every_level() {
+        let vectors = [
+            ("L", KEYGEN_L),
+            ("M", KEYGEN_M),
+            ("Q", KEYGEN_Q),
+            ("H", KEYGEN_H),
+        ];
+        for (level, expected) in vectors {
+            let code = QrCode::encode_bytes(b"keygen", ErrorCorrection::from_str(level).unwrap()).unwrap();
+            assert_eq!(rows(&code), expected.to_vec(), "level {}", level);
+        }
+    }
+
+    #[test]
+    fn known_answer_version_7() {
+        let code = QrCode::encode_bytes(WIFI_V7_PAYLOAD.as_bytes(), ErrorCorrection::Medium).unwrap();
+        assert_eq!(code.size, 45);
+        assert_eq!(hex_rows(&code), WIFI_V7_M.to_vec());
+    }
+}
@@ -1,4 +1,4 @@
-#[path = "hmac_drbg.rs"] mod hmac_drbg;
+#[path = "hmac_drbg.rs"] pub mod hmac_drbg;
 use hmac_drbg::HmacDrbg;
 
 use std::fmt;
@@ -8,9 +8,10 @@ use std::fs::read_to_string;
 use std::time::{SystemTime, UNIX_EPOCH};
 use tiny_keccak::Hasher;
 use tiny_keccak::Sha3;
-use lazy_static::lazy_static;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
 use zeroize::Zeroize;
 use getrandom::getrandom;
+use num_bigint::BigUint;
 
 
 
@@ -111,7 +112,7 @@ pub fn generate_u64_os() -> Option<u64> {
 
     check_entropy_pool();
 
-    if let Ok(_) = getrandom(&mut random_bytes) {
+    if getrandom(&mut random_bytes).is_ok() {
         let random_u64 = u64::from_le_bytes(random_bytes);
         Some(random_u64)
     } else {
@@ -168,8 +169,7 @@ pub fn generate_u64_cpujitter() -> Option<u64> {
     sha3.finalize(&mut hash_result);
 
     // Return the first 64 bits as u64
-    let random_value = vec_u8_to_u64(&hash_result[..8]);
-    random_value
+    vec_u8_to_u64(&hash_result[..8])
 }
 
 
@@ -213,12 +213,17 @@ pub fn generate_u64_cpujitter_raw() -> Option<u64> {
 
 
 
-lazy_static! {
-    static ref PREVIOUS_TIMESTAMP: std::sync::Mutex<(u64, u32)> = std::sync::Mutex::new((0, 0));
+static PERSONALIZATION_COUNTER: AtomicU64 = AtomicU64::new(0);
+static THREAD_COUNTER: AtomicU32 = AtomicU32::new(0);
+
+thread_local! {
+    static THREAD_INDEX: u32 = THREAD_COUNTER.fetch_add(1, Ordering::Relaxed);
 }
 
-/* Personalization string combines a fixed string ("kissa123", Finnish for cat123) and both seconds and nanoseconds
-   of current timestamp. This ensures that the personalization string is unique for each call.
+/* Personalization string combines a fixed string ("kissa123", Finnish for cat123), both seconds and nanoseconds
+   of current timestamp, a process wide call counter and the index of the calling thread.
+   The counter makes the personalization string unique for each call, even when the clock does not advance
+   between calls or several threads land on the same nanosecond.
 */
 fn generate_personalization_string() -> [u8; 32] {
     let mut personalization_string: [u8; 32] = [0; 32];
@@ -227,14 +232,8 @@ fn generate_personalization_string() -> [u8; 32] {
     let timestamp_secs = timestamp.as_secs();
     let timestamp_nanos = timestamp.subsec_nanos();
 
-    // Compare with previous timestamp
-    let mut prev_timestamp = PREVIOUS_TIMESTAMP.lock().unwrap();
-    if timestamp_secs < prev_timestamp.0 || (timestamp_secs == prev_timestamp.0 && timestamp_nanos <= prev_timestamp.1) {
-        eprintln!("Error: time went backwards. Exiting.");
-        std::process::exit(1);
-    }
-
-    *prev_timestamp = (timestamp_secs, timestamp_nanos);
+    let counter = PERSONALIZATION_COUNTER.fetch_add(1, Ordering::Relaxed);
+    let thread_index = THREAD_INDEX.with(|index| *index);
 
     // Copy the string bytes
     let hardcoded_str = "kissa123";
@@ -248,13 +247,27 @@ fn generate_personalization_string() -> [u8; 32] {
     let nanos_range = hardcoded_str.len() + 8..hardcoded_str.len() + 12;
     personalization_string[nanos_range].copy_from_slice(&timestamp_nanos.to_le_bytes());
 
+    // Copy the call counter bytes
+    let counter_range = hardcoded_str.len() + 12..hardcoded_str.len() + 20;
+    personalization_string[counter_range].copy_from_slice(&counter.to_le_bytes());
+
+    // Copy the thread index bytes
+    let thread_range = hardcoded_str.len() + 20..hardcoded_str.len() + 24;
+    personalization_string[thread_range].copy_from_slice(&thread_index.to_le_bytes());
+
     personalization_string
 }
 
 
 
 
-// Generate a random u64 combining three different sources
+/* The generator behind generate_u64(), for reports of how a secret was made */
+pub const DRBG_NAME: &str = "HMAC_DRBG-SHA256 seeded from os, rdrand and cpujitter";
+
+/* Generate a random u64 combining three different sources.
+   Safe to call from several threads at once. Each call instantiates its own HMAC DRBG, so no generator
+   state is shared between calls or threads.
+*/
 pub fn generate_u64() -> Option<u64> {
 
     // Generate a 1536 bit seed from three different random number sources.
@@ -287,4 +300,107 @@ pub fn generate_u64() -> Option<u64> {
     seed.zeroize();
 
     random_value
-}
\ No newline at end of file
+}
+
+/* Return a random index in range 0..n using the combined generator.
+   Values that would result in modulo bias are discarded and a new value is pulled.
+*/
+pub fn generate_index(n: usize) -> usize {
+    // The largest multiple of n that fits, so every index has the same number of accepted values
+    let limit = u64::MAX - u64::MAX % n as u64;
+    loop {
+        let value = generate_u64().unwrap();
+        if value < limit {
+            return (value % n as u64) as usize;
+        }
+    }
+}
+
+
+/* Return n random bytes from the combined generator, 8 bytes per generate_u64() call */
+pub fn generate_bytes(n: usize) -> Vec<u8> {
+    let mut bytes: Vec<u8> = Vec::with_capacity(n + 8);
+    while bytes.len() < n {
+        let mut value = generate_u64().unwrap().to_be_bytes();
+        bytes.extend_from_slice(&value);
+        value.zeroize();
+    }
+
+    // Wipe the unused tail before shrinking
+    bytes[n..].zeroize();
+    bytes.truncate(n);
+    bytes
+}
+
+
+/* Return a random number in range 0..n using the combined generator.
+   Values drawn with the bit length of n that are not below n are discarded and drawn again.
+*/
+pub fn generate_biguint_below(n: &BigUint) -> BigUint {
+    let bits = n.bits();
+    let mut bytes = generate_bytes(bits.div_ceil(8) as usize);
+    loop {
+        // Clear the bits above the bit length of n
+        if !bits.is_multiple_of(8) {
+            bytes[0] &= (1u8 << (bits % 8)) - 1;
+        }
+        let value = BigUint::from_bytes_be(&bytes);
+        bytes.zeroize();
+        if &value < n {
+            return value;
+        }
+        bytes = generate_bytes(bits.div_ceil(8) as usize);
+    }
+}
+
+
+/* A DRBG seeded from the combined generator, for statistical estimates that need many values fast.
+   Never used for the generated secrets themselves. A new DRBG is instantiated before the reseed interval runs out.
+*/
+pub struct EstimationRng {
+    drbg: HmacDrbg,
+    buffer: Vec<u8>,
+    bytes_generated: usize,
+}
+
+const ESTIMATION_BLOCK: usize = 4096;
+const ESTIMATION_BYTES_PER_SEED: usize = 512 * 1024;
+
+fn seeded_drbg() -> HmacDrbg {
+    let mut seed = generate_bytes(48);
+    let mut personalization_string = generate_personalization_string();
+    let drbg = HmacDrbg::new(&seed, &personalization_string);
+    seed.zeroize();
+    personalization_string.zeroize();
+    drbg
+}
+
+impl EstimationRng {
+    pub fn new() -> Self {
+        EstimationRng { drbg: seeded_drbg(), buffer: Vec::new(), bytes_generated: 0 }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        if self.buffer.len() < 8 {
+            if self.bytes_generated >= ESTIMATION_BYTES_PER_SEED {
+                self.drbg = seeded_drbg();
+                self.bytes_generated = 0;
+            }
+            self.buffer = self.drbg.generate_bytes(ESTIMATION_BLOCK);
+            self.bytes_generated += ESTIMATION_BLOCK;
+        }
+        let bytes = self.buffer.split_off(self.buffer.len() - 8);
+        vec_u8_to_u64(&bytes).unwrap()
+    }
+
+    /* Index in range 0..n, without modulo bias */
+    pub fn index(&mut self, n: usize) -> usize {
+        let limit = u64::MAX - u64::MAX % n as u64;
+        loop {
+            let value = self.next_u64();
+            if value < limit {
+                return (value % n as u64) as usize;
+            }
+        }
+    }
+}
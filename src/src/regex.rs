@@ -0,0 +1,456 @@
+/* regex.rs
+
+   Generates strings uniformly from a bounded regular expression, e.g. "[A-HJ-NP-Z2-9]{5}(-[A-HJ-NP-Z2-9]{5}){3}".
+
+   Supported: literals, escapes, ".", classes [...] with ranges and ^ negation, \d \w \s and their negations,
+   groups (...) and (?:...), alternation |, and the quantifiers ?, {n} and {n,m}. Unbounded quantifiers
+   (*, +, {n,}) are refused, so the language is always finite. "." and negations are taken over printable ASCII.
+
+   The expression is compiled to an NFA and then to a DFA. In the DFA every string has exactly one path, so
+   counting paths counts strings even when the expression is ambiguous (like "a|a"). Sampling walks the DFA
+   choosing each step in proportion to the number of strings behind it, which is uniform over the language.
+*/
+
+use std::collections::{BTreeSet, HashMap};
+
+use num_bigint::BigUint;
+use num_traits::{One, Zero};
+
+use crate::random;
+
+enum Node {
+    Set(Vec<char>),
+    Concat(Vec<Node>),
+    Alt(Vec<Node>),
+    Repeat(Box<Node>, usize, usize),
+}
+
+/* Limits on the NFA states, which bound the length of the strings, and on the DFA states. Counting keeps
+   the number of strings of every length for every DFA state, so the memory grows with their product.
+*/
+const MAX_NFA_STATES: usize = 4096;
+const MAX_DFA_STATES: usize = 65536;
+
+impl Node {
+    /* Number of NFA states the node expands to, saturating */
+    fn states(&self) -> usize {
+        match self {
+            Node::Set(_) => 1,
+            Node::Concat(items) => items.iter().fold(0, |sum: usize, item| sum.saturating_add(item.states())),
+            Node::Alt(branches) => branches.iter().fold(1, |sum: usize, branch| sum.saturating_add(branch.states())),
+            Node::Repeat(inner, _, max) => inner.states().saturating_mul(*max).saturating_add(1),
+        }
+    }
+}
+
+fn printable_ascii() -> Vec<char> {
+    (' '..='~').collect()
+}
+
+fn negate(set: &[char]) -> Vec<char> {
+    printable_ascii().into_iter().filter(|c| !set.contains(c)).collect()
+}
+
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek();
+        self.pos += 1;
+        c
+    }
+
+    fn parse_alt(&mut self) -> Result<Node, String> {
+        let mut branches = vec![self.parse_concat()?];
+        while self.peek() == Some('|') {
+            self.pos += 1;
+            branches.push(self.parse_concat()?);
+        }
+        Ok(if branches.len() == 1 { branches.pop().unwrap() } else { Node::Alt(branches) })
+    }
+
+    fn parse_concat(&mut self) -> Result<Node, String> {
+        let mut items = Vec::new();
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            let atom = self.parse_atom()?;
+            items.push(self.parse_quantifier(atom)?);
+        }
+        Ok(Node::Concat(items))
+    }
+
+    fn parse_atom(&mut self) -> Result<Node, String> {
+        let c = self.next().unwrap();
+        match c {
+            '(' => {
+                if self.chars[self.pos..].starts_with(&['?', ':']) {
+                    self.pos += 2;
+                }
+                let node = self.parse_alt()?;
+                if self.next() != Some(')') {
+                    return Err("missing ')'".to_string());
+                }
+                Ok(node)
+            }
+            '[' => self.parse_class(),
+            '.' => Ok(Node::Set(printable_ascii())),
+            '\\' => Ok(Node::Set(self.parse_escape()?)),
+            '*' | '+' => Err(format!("unbounded quantifier '{}' is not supported", c)),
+            '?' | '{' => Err(format!("quantifier '{}' without anything to repeat", c)),
+            // Anchors are implied, the whole string always matches
+            '^' if self.pos == 1 => Ok(Node::Concat(Vec::new())),
+            '$' if self.pos == self.chars.len() => Ok(Node::Concat(Vec::new())),
+            _ => Ok(Node::Set(vec![c])),
+        }
+    }
+
+    fn parse_escape(&mut self) -> Result<Vec<char>, String> {
+        let digits: Vec<char> = ('0'..='9').collect();
+        let word: Vec<char> = ('a'..='z').chain('A'..='Z').chain('0'..='9').chain(['_']).collect();
+        match self.next() {
+            Some('d') => Ok(digits),
+            Some('D') => Ok(negate(&digits)),
+            Some('w') => Ok(word),
+            Some('W') => Ok(negate(&word)),
+            Some('s') => Ok(vec![' ']),
+            Some('S') => Ok(negate(&[' '])),
+            Some(c) if c.is_ascii_alphanumeric() => Err(format!("unsupported escape \\{}", c)),
+            Some(c) => Ok(vec![c]),
+            None => Err("expression ends with '\\'".to_string()),
+        }
+    }
+
+    fn parse_class(&mut self) -> Result<Node, String> {
+        let negated = self.peek() == Some('^');
+        if negated {
+            self.pos += 1;
+        }
+
+        let mut set: Vec<char> = Vec::new();
+        let mut first = true;
+        loop {
+            let c = self.next().ok_or_else(|| "missing ']'".to_string())?;
+            if c == ']' && !first {
+                break;
+            }
+            first = false;
+
+            let start: Vec<char> = if c == '\\' { self.parse_escape()? } else { vec![c] };
+
+            // Range, unless the dash is the last character of the class
+            if start.len() == 1 && self.peek() == Some('-') && self.chars.get(self.pos + 1).is_some_and(|&n| n != ']') {
+                self.pos += 1;
+                let end = match self.next().unwrap() {
+                    '\\' => {
+                        let escaped = self.parse_escape()?;
+                        if escaped.len() != 1 {
+                            return Err("invalid range end".to_string());
+                        }
+                        escaped[0]
+                    }
+                    e => e,
+                };
+                if end < start[0] {
+                    return Err(format!("invalid range {}-{}", start[0], end));
+                }
+                set.extend(start[0]..=end);
+            } else {
+                set.extend(start);
+            }
+        }
+
+        set.sort();
+        set.dedup();
+        Ok(Node::Set(if negated { negate(&set) } else { set }))
+    }
+
+    fn parse_number(&mut self) -> Option<usize> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        self.chars[start..self.pos].iter().collect::<String>().parse().ok()
+    }
+
+    fn parse_quantifier(&mut self, atom: Node) -> Result<Node, String> {
+        let node = match self.peek() {
+            Some('?') => {
+                self.pos += 1;
+                Node::Repeat(Box::new(atom), 0, 1)
+            }
+            Some('*') | Some('+') => return Err(format!("unbounded quantifier '{}' is not supported", self.peek().unwrap())),
+            Some('{') => {
+                self.pos += 1;
+                let min = self.parse_number().ok_or_else(|| "invalid {n,m} quantifier".to_string())?;
+                let max = match self.next() {
+                    Some('}') => min,
+                    Some(',') => {
+                        let max = self.parse_number().ok_or_else(|| "unbounded quantifier {n,} is not supported".to_string())?;
+                        if self.next() != Some('}') {
+                            return Err("invalid {n,m} quantifier".to_string());
+                        }
+                        max
+                    }
+                    _ => return Err("invalid {n,m} quantifier".to_string()),
+                };
+                if max < min {
+                    return Err(format!("invalid quantifier {{{},{}}}", min, max));
+                }
+                Node::Repeat(Box::new(atom), min, max)
+            }
+            _ => return Ok(atom),
+        };
+
+        if matches!(self.peek(), Some('?') | Some('*') | Some('+') | Some('{')) {
+            return Err("nested quantifiers are not supported, use a group".to_string());
+        }
+        Ok(node)
+    }
+}
+
+
+/* Thompson NFA: each state has character set transitions and epsilon transitions */
+#[derive(Default)]
+struct Nfa {
+    sets: Vec<Vec<(usize, usize)>>,
+    epsilons: Vec<Vec<usize>>,
+    charsets: Vec<Vec<char>>,
+}
+
+impl Nfa {
+    fn add_state(&mut self) -> usize {
+        self.sets.push(Vec::new());
+        self.epsilons.push(Vec::new());
+        self.sets.len() - 1
+    }
+
+    /* Adds the node between from and a new state, returns the new state */
+    fn build(&mut self, node: &Node, from: usize) -> usize {
+        match node {
+            Node::Set(chars) => {
+                let to = self.add_state();
+                self.charsets.push(chars.clone());
+                self.sets[from].push((self.charsets.len() - 1, to));
+                to
+            }
+            Node::Concat(items) => items.iter().fold(from, |state, item| self.build(item, state)),
+            Node::Alt(branches) => {
+                let to = self.add_state();
+                for branch in branches {
+                    let end = self.build(branch, from);
+                    self.epsilons[end].push(to);
+                }
+                to
+            }
+            Node::Repeat(inner, min, max) => {
+                let mut state = from;
+                for _ in 0..*min {
+                    state = self.build(inner, state);
+                }
+                let to = self.add_state();
+                for _ in *min..*max {
+                    self.epsilons[state].push(to);
+                    state = self.build(inner, state);
+                }
+                self.epsilons[state].push(to);
+                to
+            }
+        }
+    }
+
+    fn closure(&self, states: &BTreeSet<usize>) -> BTreeSet<usize> {
+        let mut result = states.clone();
+        let mut stack: Vec<usize> = states.iter().copied().collect();
+        while let Some(state) = stack.pop() {
+            for &next in &self.epsilons[state] {
+                if result.insert(next) {
+                    stack.push(next);
+                }
+            }
+        }
+        result
+    }
+}
+
+
+struct DfaState {
+    accepting: bool,
+    transitions: Vec<(Vec<char>, usize)>,
+}
+
+pub struct RegexGenerator {
+    states: Vec<DfaState>,
+    counts: Vec<BigUint>,
+    length_counts: Vec<BigUint>,
+}
+
+/* Parses the expression and builds the counting DFA */
+pub fn compile(pattern: &str) -> Result<RegexGenerator, String> {
+    let mut parser = Parser { chars: pattern.chars().collect(), pos: 0 };
+    let ast = parser.parse_alt()?;
+    if parser.pos < parser.chars.len() {
+        return Err("unmatched ')'".to_string());
+    }
+    if ast.states() >= MAX_NFA_STATES {
+        return Err(format!("the expression is too large, the limit is {} positions with the quantifiers expanded", MAX_NFA_STATES));
+    }
+
+    let mut nfa = Nfa::default();
+    let start = nfa.add_state();
+    let end = nfa.build(&ast, start);
+
+    // Subset construction
+    let mut states: Vec<DfaState> = Vec::new();
+    let mut ids: HashMap<BTreeSet<usize>, usize> = HashMap::new();
+    let mut pending: Vec<BTreeSet<usize>> = Vec::new();
+
+    let start_set = nfa.closure(&BTreeSet::from([start]));
+    ids.insert(start_set.clone(), 0);
+    states.push(DfaState { accepting: start_set.contains(&end), transitions: Vec::new() });
+    pending.push(start_set);
+
+    while let Some(set) = pending.pop() {
+        let id = ids[&set];
+
+        // Group the characters by the state they lead to
+        let mut targets: HashMap<char, BTreeSet<usize>> = HashMap::new();
+        for &state in &set {
+            for &(charset, to) in &nfa.sets[state] {
+                for &c in &nfa.charsets[charset] {
+                    targets.entry(c).or_default().insert(to);
+                }
+            }
+        }
+        let mut grouped: HashMap<BTreeSet<usize>, Vec<char>> = HashMap::new();
+        for (c, to) in targets {
+            grouped.entry(nfa.closure(&to)).or_default().push(c);
+        }
+
+        let mut transitions = Vec::new();
+        for (next_set, mut chars) in grouped {
+            chars.sort();
+            let next_id = match ids.get(&next_set) {
+                Some(&next_id) => next_id,
+                None => {
+                    if states.len() >= MAX_DFA_STATES {
+                        return Err(format!("the expression is too complex, it needs more than {} DFA states", MAX_DFA_STATES));
+                    }
+                    let next_id = states.len();
+                    states.push(DfaState { accepting: next_set.contains(&end), transitions: Vec::new() });
+                    ids.insert(next_set.clone(), next_id);
+                    pending.push(next_set);
+                    next_id
+                }
+            };
+            transitions.push((chars, next_id));
+        }
+        transitions.sort();
+        states[id].transitions = transitions;
+    }
+
+    let (counts, length_counts) = count_strings(&states);
+    if counts[0].is_zero() {
+        return Err("the expression matches nothing".to_string());
+    }
+
+    Ok(RegexGenerator { states, counts, length_counts })
+}
+
+/* Number of accepted strings from each state, and the number of strings of each length from the start.
+   The language is finite, so the DFA has no cycles and a depth first post-order is a valid evaluation order.
+   The walk uses an explicit stack, as the DFA of a long expression is as deep as its longest string.
+*/
+fn count_strings(states: &[DfaState]) -> (Vec<BigUint>, Vec<BigUint>) {
+    let mut by_length: Vec<Option<Vec<BigUint>>> = vec![None; states.len()];
+
+    for root in 0..states.len() {
+        // (state, whether its successors have been counted)
+        let mut stack: Vec<(usize, bool)> = vec![(root, false)];
+        while let Some((id, expanded)) = stack.pop() {
+            if by_length[id].is_some() {
+                continue;
+            }
+            if !expanded {
+                stack.push((id, true));
+                for (_, next) in &states[id].transitions {
+                    if by_length[*next].is_none() {
+                        stack.push((*next, false));
+                    }
+                }
+                continue;
+            }
+
+            let mut lengths: Vec<BigUint> = vec![if states[id].accepting { BigUint::one() } else { BigUint::zero() }];
+            for (chars, next) in &states[id].transitions {
+                let next_lengths = by_length[*next].as_ref().unwrap();
+                if lengths.len() < next_lengths.len() + 1 {
+                    lengths.resize(next_lengths.len() + 1, BigUint::zero());
+                }
+                for (length, count) in next_lengths.iter().enumerate() {
+                    lengths[length + 1] += count * BigUint::from(chars.len());
+                }
+            }
+            by_length[id] = Some(lengths);
+        }
+    }
+
+    let by_length: Vec<Vec<BigUint>> = by_length.into_iter().map(|lengths| lengths.unwrap()).collect();
+    let counts = by_length.iter().map(|lengths| lengths.iter().sum()).collect();
+    (counts, by_length[0].clone())
+}
+
+
+impl RegexGenerator {
+    pub fn language_size(&self) -> &BigUint {
+        &self.counts[0]
+    }
+
+    /* Number of matching strings of each length, index is the length */
+    pub fn length_counts(&self) -> &[BigUint] {
+        &self.length_counts
+    }
+
+    /* Picks one of the matching strings uniformly */
+    pub fn generate(&self) -> String {
+        let mut output = String::new();
+        let mut state = 0;
+        let mut r = random::generate_biguint_below(&self.counts[0]);
+
+        loop {
+            let current = &self.states[state];
+            if current.accepting {
+                if r.is_zero() {
+                    return output;
+                }
+                r -= 1u32;
+            }
+
+            let mut chosen = None;
+            for (chars, next) in &current.transitions {
+                let per_char = &self.counts[*next];
+                let weight = per_char * BigUint::from(chars.len());
+                if r < weight {
+                    let char_index = &r / per_char;
+                    r %= per_char;
+                    chosen = Some((chars[usize::try_from(char_index).unwrap()], *next));
+                    break;
+                }
+                r -= weight;
+            }
+
+            let (c, next) = chosen.expect("Regex sampling ran out of strings");
+            output.push(c);
+            state = next;
+        }
+    }
+}
@@ -0,0 +1,213 @@
+/* secure_file.rs
+
+   Writes secrets to files. The file must not exist beforehand (O_CREAT | O_EXCL, which also
+   refuses symlinks), it is created with mode 0600 on Unix and synced to disk before returning.
+   Directories for one secret per file are created with mode 0700 and may not be symlinks. On Unix the
+   directory is opened once without following symlinks and the files are created relative to that handle
+   (openat), so the directory can not be swapped for a symlink between the check and the writes.
+
+   shred_file() overwrites a file with random data before unlinking it. That only helps where
+   writes go back to the same blocks: ext4, xfs and similar on a plain disk. Copy-on-write and
+   log-structured filesystems (btrfs, zfs, f2fs), network filesystems and SSD wear leveling keep
+   the old blocks around, so a warning is given where the filesystem is known to do that.
+*/
+
+use std::fs::{File, OpenOptions};
+use std::io::{Seek, SeekFrom, Write};
+use std::path::Path;
+
+#[cfg(unix)]
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt};
+
+use getrandom::getrandom;
+
+const SHRED_BLOCK: usize = 64 * 1024;
+
+/* Creates a new file with mode 0600, refusing existing files and symlinks */
+pub fn create_secret_file(path: &str) -> Result<File, String> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+
+    #[cfg(unix)]
+    options.mode(0o600);
+
+    options.open(path).map_err(|e| {
+        if e.kind() == std::io::ErrorKind::AlreadyExists {
+            format!("{} already exists, refusing to overwrite", path)
+        } else {
+            format!("Failed to create {}: {}", path, e)
+        }
+    })
+}
+
+/* Creates the file, writes data and syncs it. A file that could not be written completely is removed,
+   so no partial secret stays behind and a retry is not refused by the existing file.
+*/
+pub fn write_secret_file(path: &str, data: &[u8]) -> Result<(), String> {
+    let mut file = create_secret_file(path)?;
+
+    let result = write_and_sync(&mut file, path, data);
+    if result.is_err() {
+        drop(file);
+        let _ = std::fs::remove_file(path);
+    }
+    result
+}
+
+fn write_and_sync(file: &mut File, path: &str, data: &[u8]) -> Result<(), String> {
+    file.write_all(data).map_err(|e| format!("Failed to write {}: {}", path, e))?;
+    file.sync_all().map_err(|e| format!("Failed to sync {}: {}", path, e))
+}
+
+/* A directory that secret files are written into */
+pub struct SecretDir {
+    path: String,
+    #[cfg(unix)]
+    handle: File,
+}
+
+/* Creates the directory with mode 0700 if it does not exist. An existing one must be a real directory. */
+pub fn prepare_secret_dir(dir: &str) -> Result<SecretDir, String> {
+    // "link/" would resolve the symlink, so the check is done on "link"
+    let trimmed = dir.trim_end_matches(['/', std::path::MAIN_SEPARATOR]);
+    let path = if trimmed.is_empty() { dir } else { trimmed };
+
+    match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_symlink() => return Err(format!("{} is a symlink, refusing to write into it", path)),
+        Ok(metadata) if !metadata.is_dir() => return Err(format!("{} is not a directory", path)),
+        Ok(_) => {}
+        Err(_) => {
+            let mut builder = std::fs::DirBuilder::new();
+            builder.recursive(true);
+            #[cfg(unix)]
+            builder.mode(0o700);
+            builder.create(path).map_err(|e| format!("Failed to create {}: {}", path, e))?;
+        }
+    }
+
+    #[cfg(unix)]
+    {
+        let mut options = OpenOptions::new();
+        options.read(true).custom_flags(libc::O_DIRECTORY | libc::O_NOFOLLOW);
+        let handle = options.open(path).map_err(|e| match e.raw_os_error() {
+            Some(libc::ELOOP) => format!("{} is a symlink, refusing to write into it", path),
+            Some(libc::ENOTDIR) => format!("{} is not a directory", path),
+            _ => format!("Failed to open {}: {}", path, e),
+        })?;
+        Ok(SecretDir { path: path.to_string(), handle })
+    }
+
+    #[cfg(not(unix))]
+    Ok(SecretDir { path: path.to_string() })
+}
+
+impl SecretDir {
+    /* Creates the new file name in the directory like create_secret_file, writes data and syncs it */
+    #[cfg(unix)]
+    pub fn write(&self, name: &str, data: &[u8]) -> Result<(), String> {
+        use std::ffi::CString;
+        use std::os::unix::io::{AsRawFd, FromRawFd};
+
+        let path = Path::new(&self.path).join(name).to_string_lossy().into_owned();
+        let c_name = CString::new(name).map_err(|_| format!("Invalid file name {}", path))?;
+        let flags = libc::O_WRONLY | libc::O_CREAT | libc::O_EXCL | libc::O_NOFOLLOW | libc::O_CLOEXEC;
+        let fd = unsafe { libc::openat(self.handle.as_raw_fd(), c_name.as_ptr(), flags, 0o600 as libc::c_uint) };
+        if fd < 0 {
+            let e = std::io::Error::last_os_error();
+            return Err(if e.kind() == std::io::ErrorKind::AlreadyExists {
+                format!("{} already exists, refusing to overwrite", path)
+            } else {
+                format!("Failed to create {}: {}", path, e)
+            });
+        }
+        let mut file = unsafe { File::from_raw_fd(fd) };
+
+        let result = write_and_sync(&mut file, &path, data);
+        if result.is_err() {
+            drop(file);
+            unsafe { libc::unlinkat(self.handle.as_raw_fd(), c_name.as_ptr(), 0) };
+        }
+        result
+    }
+
+    #[cfg(not(unix))]
+    pub fn write(&self, name: &str, data: &[u8]) -> Result<(), String> {
+        write_secret_file(&Path::new(&self.path).join(name).to_string_lossy(), data)
+    }
+}
+
+/* Why overwriting does not reach the old blocks on this filesystem, if known */
+#[cfg(target_os = "linux")]
+pub fn overwrite_warning(path: &str) -> Option<&'static str> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = CString::new(Path::new(path).as_os_str().as_bytes()).ok()?;
+    let mut stats: libc::statfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statfs(c_path.as_ptr(), &mut stats) } != 0 {
+        return None;
+    }
+    match stats.f_type as u64 {
+        0x9123683e => Some("btrfs is copy-on-write"),
+        0x2fc12fc1 => Some("zfs is copy-on-write"),
+        0xf2f52010 => Some("f2fs is log-structured"),
+        0x6969 => Some("nfs stores the data on another machine"),
+        0xff534d42 | 0xfe534d42 => Some("cifs stores the data on another machine"),
+        0x794c7630 => Some("overlayfs may keep a copy in a lower layer"),
+        _ => None,
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn overwrite_warning(_path: &str) -> Option<&'static str> {
+    None
+}
+
+/* Overwrites a regular file with random data passes times, syncing after each pass,
+   then truncates it, renames it to a random name and unlinks it.
+*/
+pub fn shred_file(path: &str, passes: usize) -> Result<(), String> {
+    let metadata = std::fs::symlink_metadata(path).map_err(|e| format!("{}: {}", path, e))?;
+    if !metadata.file_type().is_file() {
+        return Err(format!("{} is not a regular file", path));
+    }
+
+    let mut options = OpenOptions::new();
+    options.write(true);
+    #[cfg(unix)]
+    options.custom_flags(libc::O_NOFOLLOW);
+    let mut file = options.open(path).map_err(|e| format!("Failed to open {}: {}", path, e))?;
+
+    let length = metadata.len();
+    let mut block = vec![0u8; SHRED_BLOCK];
+    for _ in 0..passes {
+        file.seek(SeekFrom::Start(0)).map_err(|e| format!("Failed to seek {}: {}", path, e))?;
+        let mut written = 0;
+        while written < length {
+            let n = (length - written).min(SHRED_BLOCK as u64) as usize;
+            getrandom(&mut block[..n]).map_err(|e| format!("Failed to get random data: {}", e))?;
+            file.write_all(&block[..n]).map_err(|e| format!("Failed to overwrite {}: {}", path, e))?;
+            written += n as u64;
+        }
+        file.sync_all().map_err(|e| format!("Failed to sync {}: {}", path, e))?;
+    }
+    file.set_len(0).map_err(|e| format!("Failed to truncate {}: {}", path, e))?;
+    file.sync_all().map_err(|e| format!("Failed to sync {}: {}", path, e))?;
+    drop(file);
+
+    // Hide the name too before the directory entry goes away
+    let mut name_bytes = [0u8; 8];
+    getrandom(&mut name_bytes).map_err(|e| format!("Failed to get random data: {}", e))?;
+    let renamed = Path::new(path).with_file_name(hex::encode(name_bytes));
+    std::fs::rename(path, &renamed).map_err(|e| format!("Failed to rename {}: {}", path, e))?;
+    std::fs::remove_file(&renamed).map_err(|e| format!("Failed to remove {}: {}", renamed.display(), e))?;
+
+    if let Some(parent) = renamed.parent() {
+        let parent = if parent.as_os_str().is_empty() { Path::new(".") } else { parent };
+        if let Ok(dir) = File::open(parent) {
+            let _ = dir.sync_all();
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,399 @@
+/* strength.rs
+
+   Strength estimate of an existing password or passphrase, for "keygen check".
+
+   Two numbers are given. The uniform estimate is the entropy the secret would have if it had been
+   generated by keygen from the smallest built-in alphabet or wordlist that can produce it. A wordlist
+   only produces lowercase words joined by one delimiter, and the delimiter adds its own bits. The pattern
+   estimate is in the style of zxcvbn: the secret is split into dictionary words (also with l33t
+   substitutions), repeats, sequences, keyboard walks, dates and leftover characters, and the split
+   needing the fewest guesses is used. The pattern estimate is what a human-made secret can be expected to resist.
+*/
+
+use std::collections::HashSet;
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::alphabet;
+use crate::bip39_wordlists;
+use crate::layout;
+use crate::normalize;
+
+pub struct UniformEstimate {
+    pub source: String,
+    pub bits: f64,
+}
+
+pub struct Segment {
+    pub start: usize,
+    pub end: usize,
+    pub pattern: String,
+    pub bits: f64,
+}
+
+pub struct PatternEstimate {
+    pub segments: Vec<Segment>,
+    pub bits: f64,
+}
+
+struct Wordlist {
+    name: &'static str,
+    words: HashSet<String>,
+    longest: usize,
+}
+
+fn wordlists() -> Vec<Wordlist> {
+    let mut lists: Vec<(&'static str, Vec<String>)> = ["words-fi", "words-en-eff", "words-en-short", "words-en-short2", "assembly", "assembly-ascii"].iter()
+        .map(|&name| (name, alphabet::alphabet_get_elements(name).unwrap()))
+        .collect();
+    lists.push(("bip39-en", bip39_wordlists::BIP39_ENGLISH.iter().map(|word| word.to_string()).collect()));
+
+    lists.into_iter().map(|(name, words)| {
+        let words: HashSet<String> = words.into_iter().map(|word| word.to_lowercase()).collect();
+        let longest = words.iter().map(|word| word.chars().count()).max().unwrap_or(0);
+        Wordlist { name, words, longest }
+    }).collect()
+}
+
+
+/* Characters a delimiter can be made of: printable ASCII that is not a letter */
+const DELIMITER_CHARS: f64 = 43.0;
+
+/* Smallest number of words of the list that make up the secret, joined by the delimiter */
+fn count_words(secret: &[char], delimiter: &[char], list: &Wordlist) -> Option<usize> {
+    // best[i]: the fewest words that make up secret[..i], ending with a word
+    let mut best: Vec<Option<usize>> = vec![None; secret.len() + 1];
+    best[0] = Some(0);
+
+    for position in 0..secret.len() {
+        let Some(words_so_far) = best[position] else { continue };
+        let start = if position == 0 {
+            0
+        } else if secret[position..].starts_with(delimiter) {
+            position + delimiter.len()
+        } else {
+            continue;
+        };
+        for end in (start + 1)..=secret.len().min(start + list.longest) {
+            let candidate: String = secret[start..end].iter().collect();
+            if list.words.contains(&candidate) && best[end].is_none_or(|b| words_so_far + 1 < b) {
+                best[end] = Some(words_so_far + 1);
+            }
+        }
+    }
+
+    best[secret.len()].filter(|&words| words > 0)
+}
+
+/* Runs of characters that are not letters, each a possible delimiter, and no delimiter at all */
+fn delimiter_candidates(secret: &[char]) -> Vec<Vec<char>> {
+    let mut candidates: Vec<Vec<char>> = vec![Vec::new()];
+    let mut run: Vec<char> = Vec::new();
+    for &c in secret.iter().chain(std::iter::once(&'a')) {
+        if !c.is_alphabetic() {
+            run.push(c);
+        } else if !run.is_empty() {
+            if !candidates.contains(&run) {
+                candidates.push(run.clone());
+            }
+            run.clear();
+        }
+    }
+    candidates
+}
+
+/* The smallest built-in alphabet or wordlist that can produce the secret, and the entropy it would have */
+pub fn uniform_estimate(secret: &str) -> Option<UniformEstimate> {
+    let graphemes: Vec<String> = secret.graphemes(true).map(|g| g.to_string()).collect();
+    let mut best: Option<UniformEstimate> = None;
+    let mut consider = |source: String, bits: f64| {
+        if best.as_ref().is_none_or(|b| bits < b.bits) {
+            best = Some(UniformEstimate { source, bits });
+        }
+    };
+
+    for name in alphabet::ALPHABET_NAMES {
+        let elements = alphabet::alphabet_get_elements(name).unwrap();
+        if elements.iter().any(|element| normalize::grapheme_count(element) != 1) {
+            continue;
+        }
+        if graphemes.iter().all(|g| elements.contains(g)) {
+            consider(format!("alphabet {} ({} symbols)", name, elements.len()), graphemes.len() as f64 * (elements.len() as f64).log2());
+        }
+    }
+
+    // Wordlists give lowercase words joined by one delimiter, which is counted as printable ASCII that is not a letter
+    if secret == secret.to_lowercase() {
+        let chars: Vec<char> = secret.chars().collect();
+        let delimiters = delimiter_candidates(&chars);
+        for list in wordlists() {
+            for delimiter in &delimiters {
+                if let Some(words) = count_words(&chars, delimiter, &list) {
+                    let delimiter_bits = delimiter.len() as f64 * DELIMITER_CHARS.log2();
+                    let source = if delimiter.is_empty() {
+                        format!("wordlist {} ({} words)", list.name, list.words.len())
+                    } else {
+                        format!("wordlist {} ({} words) with a {} character delimiter", list.name, list.words.len(), delimiter.len())
+                    };
+                    consider(source, words as f64 * (list.words.len() as f64).log2() + delimiter_bits);
+                }
+            }
+        }
+    }
+
+    best
+}
+
+
+/* Characters l33t speak uses for letters */
+fn unleet(c: char) -> Option<char> {
+    match c {
+        '4' | '@' => Some('a'),
+        '3' => Some('e'),
+        '1' | '!' => Some('i'),
+        '0' => Some('o'),
+        '$' | '5' => Some('s'),
+        '7' | '+' => Some('t'),
+        _ => None,
+    }
+}
+
+fn class_size(c: char) -> f64 {
+    if c.is_ascii_lowercase() || c.is_ascii_uppercase() {
+        26.0
+    } else if c.is_ascii_digit() {
+        10.0
+    } else if c.is_ascii() {
+        33.0
+    } else {
+        100.0
+    }
+}
+
+/* A pattern match over chars[start..end] and the number of guesses it takes */
+struct Match {
+    start: usize,
+    end: usize,
+    pattern: String,
+    guesses: f64,
+}
+
+fn dictionary_matches(chars: &[char], lists: &[Wordlist], matches: &mut Vec<Match>) {
+    for list in lists {
+        for start in 0..chars.len() {
+            for end in (start + 3)..=chars.len().min(start + list.longest) {
+                let original = &chars[start..end];
+                let lower: String = original.iter().flat_map(|c| c.to_lowercase()).collect();
+                let unleeted: String = original.iter()
+                    .map(|&c| unleet(c).unwrap_or(c))
+                    .flat_map(|c| c.to_lowercase())
+                    .collect();
+
+                let (word, leet) = if list.words.contains(&lower) {
+                    (lower, false)
+                } else if list.words.contains(&unleeted) {
+                    (unleeted, true)
+                } else {
+                    continue;
+                };
+
+                // Capitalizing the first letter doubles the guesses, other mixed case costs a guess per letter
+                let uppercase = original.iter().filter(|c| c.is_uppercase()).count();
+                let case_guesses = match uppercase {
+                    0 => 1.0,
+                    1 if original[0].is_uppercase() => 2.0,
+                    _ if uppercase == original.len() => 2.0,
+                    _ => 2f64.powi(original.len() as i32),
+                };
+                let leet_guesses = if leet {
+                    2f64.powi(original.iter().filter(|c| unleet(**c).is_some()).count() as i32)
+                } else {
+                    1.0
+                };
+
+                matches.push(Match {
+                    start,
+                    end,
+                    pattern: format!("{} word of {} letters{}", list.name, word.chars().count(), if leet { ", l33t" } else { "" }),
+                    guesses: list.words.len() as f64 * case_guesses * leet_guesses,
+                });
+            }
+        }
+    }
+}
+
+fn repeat_matches(chars: &[char], matches: &mut Vec<Match>) {
+    let mut start = 0;
+    while start < chars.len() {
+        let mut end = start + 1;
+        while end < chars.len() && chars[end] == chars[start] {
+            end += 1;
+        }
+        if end - start >= 3 {
+            matches.push(Match { start, end, pattern: format!("repeat of {} characters", end - start), guesses: class_size(chars[start]) * (end - start) as f64 });
+        }
+        start = end;
+    }
+}
+
+fn sequence_matches(chars: &[char], matches: &mut Vec<Match>) {
+    for start in 0..chars.len() {
+        for step in [1i32, -1] {
+            let mut end = start + 1;
+            while end < chars.len()
+                && chars[end].is_ascii_alphanumeric()
+                && chars[end] as i32 - chars[end - 1] as i32 == step {
+                end += 1;
+            }
+            if end - start >= 3 && chars[start].is_ascii_alphanumeric() {
+                matches.push(Match { start, end, pattern: format!("sequence of {} characters", end - start), guesses: class_size(chars[start]) * 2.0 * (end - start) as f64 });
+            }
+        }
+    }
+}
+
+fn keyboard_matches(chars: &[char], matches: &mut Vec<Match>) {
+    let lower: Vec<char> = chars.iter().map(|c| c.to_ascii_lowercase()).collect();
+    let mut rows: Vec<Vec<char>> = Vec::new();
+    for name in ["us", "de", "fi"] {
+        for row in layout::get_layout(name).unwrap().rows() {
+            rows.push(row.chars().collect());
+        }
+    }
+    let adjacent = |a: char, b: char| rows.iter().any(|row| row.windows(2).any(|pair| a != ' ' && (pair == [a, b] || pair == [b, a])));
+
+    let mut start = 0;
+    while start < lower.len() {
+        let mut end = start + 1;
+        while end < lower.len() && adjacent(lower[end - 1], lower[end]) {
+            end += 1;
+        }
+        if end - start >= 4 {
+            // Start key, direction and length
+            matches.push(Match { start, end, pattern: format!("keyboard walk of {} keys", end - start), guesses: 47.0 * 2.0 * (end - start) as f64 });
+        }
+        start = end;
+    }
+}
+
+fn date_matches(chars: &[char], matches: &mut Vec<Match>) {
+    let is_year = |s: &[char]| {
+        let year: String = s.iter().collect();
+        year.parse::<u32>().is_ok_and(|y| (1900..=2099).contains(&y))
+    };
+
+    for start in 0..chars.len() {
+        // Years alone
+        if start + 4 <= chars.len() && chars[start..start + 4].iter().all(|c| c.is_ascii_digit()) && is_year(&chars[start..start + 4]) {
+            matches.push(Match { start, end: start + 4, pattern: "year".to_string(), guesses: 200.0 });
+        }
+        // Day, month and year, with or without separators, in either order
+        for length in [8, 10] {
+            if start + length > chars.len() {
+                continue;
+            }
+            let s = &chars[start..start + length];
+            let digits: Vec<char> = s.iter().copied().filter(|c| c.is_ascii_digit()).collect();
+            let separators_ok = length == 8 && digits.len() == 8
+                || length == 10 && digits.len() == 8 && ((s[2] == s[5] && !s[2].is_ascii_digit()) || (s[4] == s[7] && !s[4].is_ascii_digit()));
+            if !separators_ok {
+                continue;
+            }
+            let day_month = |d: &[char]| {
+                let day: u32 = d[0..2].iter().collect::<String>().parse().unwrap();
+                let month: u32 = d[2..4].iter().collect::<String>().parse().unwrap();
+                (1..=31).contains(&day) && (1..=12).contains(&month)
+            };
+            let year_last = is_year(&digits[4..8]) && day_month(&digits[0..4]);
+            let year_first = is_year(&digits[0..4]) && (day_month(&[digits[6], digits[7], digits[4], digits[5]]));
+            if year_last || year_first {
+                matches.push(Match { start, end: start + length, pattern: "date".to_string(), guesses: 366.0 * 200.0 * if length == 10 { 4.0 } else { 1.0 } });
+            }
+        }
+    }
+}
+
+/* Splits the secret into the patterns needing the fewest guesses in total */
+pub fn pattern_estimate(secret: &str) -> PatternEstimate {
+    let chars: Vec<char> = secret.chars().collect();
+    let lists = wordlists();
+
+    let mut matches: Vec<Match> = Vec::new();
+    dictionary_matches(&chars, &lists, &mut matches);
+    repeat_matches(&chars, &mut matches);
+    sequence_matches(&chars, &mut matches);
+    keyboard_matches(&chars, &mut matches);
+    date_matches(&chars, &mut matches);
+    // Leftover characters are guessed from every class the secret uses
+    let mut cardinality = 0;
+    if chars.iter().any(|c| c.is_ascii_lowercase()) {
+        cardinality += 26;
+    }
+    if chars.iter().any(|c| c.is_ascii_uppercase()) {
+        cardinality += 26;
+    }
+    if chars.iter().any(|c| c.is_ascii_digit()) {
+        cardinality += 10;
+    }
+    if chars.iter().any(|c| c.is_ascii() && !c.is_ascii_alphanumeric()) {
+        cardinality += 33;
+    }
+    if chars.iter().any(|c| !c.is_ascii()) {
+        cardinality += 100;
+    }
+    for i in 0..chars.len() {
+        matches.push(Match { start: i, end: i + 1, pattern: "character".to_string(), guesses: cardinality as f64 });
+    }
+
+    // best[i]: the fewest bits to cover chars[..i], and the match ending there
+    let mut best: Vec<(f64, Option<usize>)> = vec![(f64::INFINITY, None); chars.len() + 1];
+    best[0] = (0.0, None);
+    for i in 1..=chars.len() {
+        for (m, candidate) in matches.iter().enumerate().filter(|(_, m)| m.end == i) {
+            let bits = best[candidate.start].0 + candidate.guesses.log2();
+            if bits < best[i].0 {
+                best[i] = (bits, Some(m));
+            }
+        }
+    }
+
+    let mut segments: Vec<Segment> = Vec::new();
+    let mut i = chars.len();
+    while i > 0 {
+        let m = &matches[best[i].1.unwrap()];
+        segments.push(Segment { start: m.start, end: m.end, pattern: m.pattern.clone(), bits: m.guesses.log2() });
+        i = m.start;
+    }
+    segments.reverse();
+
+    // Runs of single characters are reported as one brute force segment
+    let mut merged: Vec<Segment> = Vec::new();
+    for segment in segments {
+        match merged.last_mut() {
+            Some(last) if last.pattern == "brute force" && segment.pattern == "character" => {
+                last.end = segment.end;
+                last.bits += segment.bits;
+            }
+            _ if segment.pattern == "character" => merged.push(Segment { pattern: "brute force".to_string(), ..segment }),
+            _ => merged.push(segment),
+        }
+    }
+
+    PatternEstimate { bits: best[chars.len()].0, segments: merged }
+}
+
+/* Prints both estimates. Only positions are shown, never the secret itself. */
+pub fn print_estimates(secret: &str) {
+    println!("Length: {} characters", normalize::grapheme_count(secret));
+
+    match uniform_estimate(secret) {
+        Some(estimate) => println!("Uniform estimate: {:.1} bits, if generated from {}", estimate.bits, estimate.source),
+        None => println!("Uniform estimate: no built-in alphabet or wordlist can produce this"),
+    }
+
+    let estimate = pattern_estimate(secret);
+    println!("Pattern estimate: {:.1} bits", estimate.bits);
+    for segment in &estimate.segments {
+        println!("  {:>3}-{:<3} {:<40} {:>6.1} bits", segment.start + 1, segment.end, segment.pattern, segment.bits);
+    }
+}
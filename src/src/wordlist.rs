@@ -0,0 +1,237 @@
+/* wordlist.rs
+
+   Loads external wordlists: UTF-8 text, one word per line.
+   Words are normalized to Unicode NFC. Empty lines and duplicates are skipped and reported,
+   words containing whitespace or control characters make the whole list invalid.
+
+   Diceware style lists ("11111<tab>abacus", as published by EFF) are also accepted.
+   The dice numbers are kept so that passphrases can be printed with the matching dice rolls.
+*/
+
+use std::fs;
+use unicode_normalization::UnicodeNormalization;
+
+use crate::normalize;
+
+pub struct WordlistReport {
+    pub words: Vec<String>,
+    pub lines: usize,
+    pub empty_lines: Vec<usize>,
+    pub duplicates: Vec<(usize, String)>,
+    pub normalized: usize,
+    pub prefixes: Vec<(String, String)>,
+    pub dice_numbers: Option<Vec<String>>,
+    pub errors: Vec<String>,
+}
+
+impl WordlistReport {
+    pub fn is_valid(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    pub fn is_prefix_free(&self) -> bool {
+        self.prefixes.is_empty()
+    }
+
+    pub fn bits_per_word(&self) -> f64 {
+        (self.words.len() as f64).log2()
+    }
+}
+
+
+/* Reads and validates the wordlist. Fails only if the file can not be read. */
+pub fn load_wordlist(path: &str) -> Result<WordlistReport, String> {
+    let data = fs::read(path).map_err(|e| format!("Failed to read wordlist {}: {}", path, e))?;
+    Ok(parse_wordlist(&data))
+}
+
+pub fn parse_wordlist(data: &[u8]) -> WordlistReport {
+    let mut report = WordlistReport {
+        words: Vec::new(),
+        lines: 0,
+        empty_lines: Vec::new(),
+        duplicates: Vec::new(),
+        normalized: 0,
+        prefixes: Vec::new(),
+        dice_numbers: None,
+        errors: Vec::new(),
+    };
+
+    let data = data.strip_prefix(b"\xef\xbb\xbf").unwrap_or(data);
+    let mut seen = std::collections::HashSet::new();
+
+    // The first word decides whether the list is in diceware format
+    let mut dice_format: Option<bool> = None;
+    let mut dice_numbers: Vec<String> = Vec::new();
+
+    // A trailing newline does not make an extra line
+    let mut raw_lines: Vec<&[u8]> = data.split(|&b| b == b'\n').collect();
+    if raw_lines.last().is_some_and(|line| line.is_empty()) {
+        raw_lines.pop();
+    }
+    report.lines = raw_lines.len();
+
+    for (i, raw_line) in raw_lines.iter().enumerate() {
+        let line_number = i + 1;
+
+        let line = match std::str::from_utf8(raw_line) {
+            Ok(line) => line.trim(),
+            Err(_) => {
+                report.errors.push(format!("line {}: not valid UTF-8", line_number));
+                continue;
+            }
+        };
+
+        if line.is_empty() {
+            report.empty_lines.push(line_number);
+            continue;
+        }
+
+        let (dice_number, line) = split_dice_number(line);
+        if *dice_format.get_or_insert(dice_number.is_some()) != dice_number.is_some() {
+            report.errors.push(format!("line {}: mixed diceware and plain lines", line_number));
+            continue;
+        }
+
+        if line.chars().any(|c| c.is_whitespace() || c.is_control()) {
+            report.errors.push(format!("line {}: word contains whitespace or control characters", line_number));
+            continue;
+        }
+
+        let word: String = line.nfc().collect();
+        if word != line {
+            report.normalized += 1;
+        }
+
+        if !seen.insert(word.clone()) {
+            report.duplicates.push((line_number, word));
+            continue;
+        }
+
+        report.words.push(word);
+        if let Some(dice_number) = dice_number {
+            dice_numbers.push(dice_number.to_string());
+        }
+    }
+
+    if dice_format == Some(true) {
+        let mut unique = std::collections::HashSet::new();
+        if dice_numbers.iter().any(|n| n.len() != dice_numbers[0].len() || !unique.insert(n)) {
+            report.errors.push("dice numbers must be unique and have the same amount of dice".to_string());
+        }
+        report.dice_numbers = Some(dice_numbers);
+    }
+
+    report.prefixes = find_prefixes(&report.words);
+
+    if report.words.len() < 2 {
+        report.errors.push(format!("wordlist has {} unique word(s), at least 2 are needed", report.words.len()));
+    }
+
+    report
+}
+
+
+/* Splits "16655<whitespace>word" into the dice number and the word */
+fn split_dice_number(line: &str) -> (Option<&str>, &str) {
+    if let Some((number, word)) = line.split_once(|c: char| c.is_whitespace()) {
+        if !number.is_empty() && number.chars().all(|c| ('1'..='6').contains(&c)) {
+            return (Some(number), word.trim_start());
+        }
+    }
+    (None, line)
+}
+
+
+/* Number of dice needed to pick a word from a list of the given size, if the size is a power of six */
+pub fn dice_per_word(count: usize) -> Option<u32> {
+    let mut dice = 0;
+    let mut size: usize = 1;
+    while size < count {
+        size = size.checked_mul(6)?;
+        dice += 1;
+    }
+    if size == count && dice > 0 { Some(dice) } else { None }
+}
+
+/* Dice rolls that select the element at index, from a list ordered like a diceware list (11111, 11112, ...) */
+pub fn dice_rolls_for_index(index: usize, dice: u32) -> String {
+    let mut rolls = vec![b'1'; dice as usize];
+    let mut value = index;
+    for roll in rolls.iter_mut().rev() {
+        *roll = b'1' + (value % 6) as u8;
+        value /= 6;
+    }
+    String::from_utf8(rolls).unwrap()
+}
+
+
+/* Returns (word, longer word) pairs where the first is a prefix of the second.
+   After sorting, a word that is a prefix of any other word is also a prefix of the word right after it.
+*/
+fn find_prefixes(words: &[String]) -> Vec<(String, String)> {
+    let mut sorted: Vec<&String> = words.iter().collect();
+    sorted.sort();
+
+    sorted.windows(2)
+        .filter(|pair| pair[1].starts_with(pair[0].as_str()))
+        .map(|pair| (pair[0].clone(), pair[1].clone()))
+        .collect()
+}
+
+
+/* Prints the diagnostics of a wordlist. Used by "keygen wordlist check". */
+pub fn print_report(path: &str, report: &WordlistReport) {
+    println!("File: {}", path);
+    println!("Lines: {}", report.lines);
+    println!("Unique words: {}", report.words.len());
+
+    println!("Empty lines: {}", report.empty_lines.len());
+    for line_number in report.empty_lines.iter().take(10) {
+        println!("  line {}", line_number);
+    }
+
+    println!("Duplicates: {}", report.duplicates.len());
+    for (line_number, word) in report.duplicates.iter().take(10) {
+        println!("  line {}: {}", line_number, word);
+    }
+
+    println!("Normalized to NFC: {}", report.normalized);
+
+    // Lengths in grapheme clusters, as typed
+    if !report.words.is_empty() {
+        let lengths: Vec<usize> = report.words.iter().map(|word| normalize::grapheme_count(word)).collect();
+        let average = lengths.iter().sum::<usize>() as f64 / lengths.len() as f64;
+        println!("Word length: {} to {} characters, {:.2} on average", lengths.iter().min().unwrap(), lengths.iter().max().unwrap(), average);
+    }
+
+    match (&report.dice_numbers, dice_per_word(report.words.len())) {
+        (Some(numbers), Some(dice)) if numbers.first().is_some_and(|n| n.len() == dice as usize) => {
+            println!("Diceware: yes, {} dice per word", dice);
+        }
+        (Some(_), _) => println!("Diceware: dice numbers do not cover all rolls, can not be used with --dice"),
+        (None, Some(dice)) => println!("Diceware: list size allows {} dice per word in list order", dice),
+        (None, None) => println!("Diceware: no"),
+    }
+
+    if report.is_prefix_free() {
+        println!("Prefix-free: yes");
+    } else {
+        println!("Prefix-free: no ({} words are prefixes of other words)", report.prefixes.len());
+        for (word, longer) in report.prefixes.iter().take(10) {
+            println!("  {} -> {}", word, longer);
+        }
+    }
+
+    for error in &report.errors {
+        println!("Error: {}", error);
+    }
+
+    if report.words.len() >= 2 {
+        let bits_per_word = report.bits_per_word();
+        println!("Bits per word: {:.4}", bits_per_word);
+        for bits in [64, 128, 256] {
+            println!("Words for {} bits: {}", bits, (bits as f64 / bits_per_word).ceil());
+        }
+    }
+}